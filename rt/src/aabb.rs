@@ -0,0 +1,163 @@
+use crate::vec3::Point3;
+use crate::ray::Ray;
+use crate::interval::Interval;
+
+// Boxes thinner than this get padded so the slab test never sees a zero-width slab
+const MIN_EXTENT: f64 = 0.0001;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb { x: Interval::EMPTY, y: Interval::EMPTY, z: Interval::EMPTY };
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Aabb {
+        let pad = |i: Interval| if i.size() < MIN_EXTENT { i.expand(MIN_EXTENT) } else { i };
+        Aabb { x: pad(x), y: pad(y), z: pad(z) }
+    }
+
+    // Box spanning two opposite corners, in any order
+    pub fn from_points(a: Point3, b: Point3) -> Aabb {
+        Aabb::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb {
+            x: Interval::enclosing(a.x, b.x),
+            y: Interval::enclosing(a.y, b.y),
+            z: Interval::enclosing(a.z, b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval {
+        match n {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    // Slab test: does the ray pass through the box anywhere inside ray_t?
+    pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let inv_d = 1.0 / r.direction[axis];
+
+            let mut t0 = (ax.min - r.origin[axis]) * inv_d;
+            let mut t1 = (ax.max - r.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use crate::hittable::Hittable;
+    use crate::hittable_list::HittableList;
+    use crate::sphere::Sphere;
+    use crate::cube::Cube;
+    use crate::cylinder::Cylinder;
+    use crate::plane::Plane;
+    use crate::material::{Material, Lambertian};
+    use crate::color::Color;
+    use crate::vec3::Vec3;
+
+    fn grey() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0))
+    }
+
+    // Every hit point of a bounded object has to lie inside its box
+    fn assert_hits_inside(object: &impl Hittable) {
+        let bbox = object.bounding_box().expect("object should be bounded");
+        let target = bbox.centroid();
+        let loose = Aabb::new(bbox.x.expand(1e-6), bbox.y.expand(1e-6), bbox.z.expand(1e-6));
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = target + 10.0 * Vec3::random_unit_vector();
+            let r = Ray::new(origin, target + Vec3::random_range(-1.0, 1.0) - origin);
+            if let Some(rec) = object.hit(&r, Interval::new(0.001, f64::INFINITY)) {
+                assert!((0..3).all(|n| loose.axis_interval(n).surrounds(rec.p[n])));
+                assert!(bbox.hit(&r, Interval::new(0.001, f64::INFINITY)));
+                hits += 1;
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn test_primitive_boxes() {
+        assert_hits_inside(&Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.7, grey()));
+        assert_hits_inside(&Cube::from_center_size(Point3::new(-1.0, 0.5, 0.0), 1.2, grey()));
+        assert_hits_inside(&Cylinder::new(Point3::new(-0.5, 0.0, -2.0), Point3::new(0.5, 2.0, -1.5), 0.6, true, grey()));
+    }
+
+    #[test]
+    fn test_unbounded_and_empty() {
+        let plane = Plane::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0), grey());
+        assert!(plane.bounding_box().is_none());
+
+        let mut list = HittableList::new();
+        assert!(list.bounding_box().is_some_and(|b| b.surface_area() == 0.0));
+        list.add(Sphere::new(Point3::new(2.0, 0.0, 0.0), 1.0, grey()));
+        list.add(Sphere::new(Point3::new(-2.0, 0.0, 0.0), 1.0, grey()));
+        let bbox = list.bounding_box().unwrap();
+        assert_eq!((bbox.x.min, bbox.x.max), (-3.0, 3.0));
+        list.add(plane);
+        assert!(list.bounding_box().is_none());
+    }
+
+    #[test]
+    fn test_flat_box_is_padded() {
+        let flat = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 1.0));
+        assert!(flat.y.size() > 0.0);
+        let r = Ray::new(Point3::new(0.5, 1.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(flat.hit(&r, Interval::new(0.0, f64::INFINITY)));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::aabb::Aabb;
+use crate::vec3::Point3;
+
+const MAX_LEAF_SIZE: usize = 4;
+const SAH_BUCKETS: usize = 12;
+// Cost of visiting a node relative to intersecting one primitive
+const TRAVERSAL_COST: f64 = 0.125;
+
+enum NodeKind {
+    Leaf { first: usize, count: usize },
+    // The left child is always stored right after its parent
+    Interior { right: usize, axis: usize },
+}
+
+struct Node {
+    bbox: Aabb,
+    kind: NodeKind,
+}
+
+struct PrimInfo {
+    index: usize,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+pub struct BvhNode {
+    nodes: Vec<Node>,
+    objects: Vec<Box<dyn Hittable>>,
+    // Objects with no bounding box (planes) can't be placed in the tree,
+    // so they are tested against every ray
+    unbounded: Vec<Box<dyn Hittable>>,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> BvhNode {
+        let mut bounded = Vec::with_capacity(list.len());
+        let mut unbounded = Vec::new();
+        let mut info = Vec::with_capacity(list.len());
+        for object in list.into_objects() {
+            match object.bounding_box() {
+                Some(bbox) => {
+                    info.push(PrimInfo { index: bounded.len(), bbox, centroid: bbox.centroid() });
+                    bounded.push(Some(object));
+                }
+                None => unbounded.push(object),
+            }
+        }
+
+        let mut bvh = BvhNode {
+            nodes: Vec::with_capacity(2 * info.len()),
+            objects: Vec::with_capacity(info.len()),
+            unbounded,
+        };
+        if !info.is_empty() {
+            bvh.build(&mut info, &mut bounded);
+        }
+        bvh
+    }
+
+    // Builds the subtree for `info` depth first and returns the index of its root
+    fn build(&mut self, info: &mut [PrimInfo], source: &mut [Option<Box<dyn Hittable>>]) -> usize {
+        let bbox = info.iter().fold(Aabb::EMPTY, |b, p| Aabb::surrounding(&b, &p.bbox));
+        let node_index = self.nodes.len();
+
+        match split(info, &bbox) {
+            None => {
+                let first = self.objects.len();
+                for p in info.iter() {
+                    self.objects.push(source[p.index].take().expect("object placed in two leaves"));
+                }
+                self.nodes.push(Node { bbox, kind: NodeKind::Leaf { first, count: info.len() } });
+            }
+            Some((axis, mid)) => {
+                self.nodes.push(Node { bbox, kind: NodeKind::Interior { right: 0, axis } });
+                let (left, right) = info.split_at_mut(mid);
+                self.build(left, source);
+                let right = self.build(right, source);
+                self.nodes[node_index].kind = NodeKind::Interior { right, axis };
+            }
+        }
+        node_index
+    }
+
+    fn hit_node(&self, index: usize, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let node = &self.nodes[index];
+        if !node.bbox.hit(r, ray_t) {
+            return None;
+        }
+        match node.kind {
+            NodeKind::Leaf { first, count } => {
+                let mut rec = None;
+                let mut closest_so_far = ray_t.max;
+                for object in &self.objects[first..first + count] {
+                    if let Some(hitrec) = object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                        closest_so_far = hitrec.t;
+                        rec = Some(hitrec);
+                    }
+                }
+                rec
+            }
+            NodeKind::Interior { right, axis } => {
+                // Visit the child nearer the ray origin first so the far one can be culled by t
+                let (near, far) = if r.direction[axis] < 0.0 { (right, index + 1) } else { (index + 1, right) };
+                let near_rec = self.hit_node(near, r, ray_t);
+                let far_max = near_rec.as_ref().map_or(ray_t.max, |rec| rec.t);
+                self.hit_node(far, r, Interval::new(ray_t.min, far_max)).or(near_rec)
+            }
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = None;
+        let mut closest_so_far = ray_t.max;
+        for object in &self.unbounded {
+            if let Some(hitrec) = object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                closest_so_far = hitrec.t;
+                rec = Some(hitrec);
+            }
+        }
+        if !self.nodes.is_empty()
+            && let Some(hitrec) = self.hit_node(0, r, Interval::new(ray_t.min, closest_so_far))
+        {
+            rec = Some(hitrec);
+        }
+        rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        Some(self.nodes.first().map_or(Aabb::EMPTY, |node| node.bbox))
+    }
+}
+
+// Picks a split with the surface area heuristic, binning centroids into buckets
+// along each axis. Partitions `info` and returns (axis, mid), or None for a leaf.
+fn split(info: &mut [PrimInfo], bbox: &Aabb) -> Option<(usize, usize)> {
+    let count = info.len();
+    if count == 1 {
+        return None;
+    }
+
+    let centroid_bounds = info.iter().fold(Aabb::EMPTY, |b, p| Aabb {
+        x: Interval::enclosing(b.x, Interval::new(p.centroid.x, p.centroid.x)),
+        y: Interval::enclosing(b.y, Interval::new(p.centroid.y, p.centroid.y)),
+        z: Interval::enclosing(b.z, Interval::new(p.centroid.z, p.centroid.z)),
+    });
+
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        let extent = centroid_bounds.axis_interval(axis);
+        if extent.size() <= 0.0 {
+            continue;
+        }
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut boxes = [Aabb::EMPTY; SAH_BUCKETS];
+        for p in info.iter() {
+            let b = bucket_index(p.centroid[axis], extent);
+            counts[b] += 1;
+            boxes[b] = Aabb::surrounding(&boxes[b], &p.bbox);
+        }
+
+        // Sweep from the right once so every split's right side is known
+        let mut right_area = [0.0; SAH_BUCKETS];
+        let mut right_count = [0usize; SAH_BUCKETS];
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for b in (1..SAH_BUCKETS).rev() {
+            acc_box = Aabb::surrounding(&acc_box, &boxes[b]);
+            acc_count += counts[b];
+            right_area[b] = acc_box.surface_area();
+            right_count[b] = acc_count;
+        }
+
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for b in 0..SAH_BUCKETS - 1 {
+            acc_box = Aabb::surrounding(&acc_box, &boxes[b]);
+            acc_count += counts[b];
+            if acc_count == 0 || right_count[b + 1] == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (acc_count as f64 * acc_box.surface_area()
+                    + right_count[b + 1] as f64 * right_area[b + 1])
+                    / bbox.surface_area();
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, b));
+            }
+        }
+    }
+
+    match best {
+        Some((cost, axis, bucket)) => {
+            if count <= MAX_LEAF_SIZE && cost >= count as f64 {
+                return None;
+            }
+            let extent = centroid_bounds.axis_interval(axis);
+            let mid = partition(info, |p| bucket_index(p.centroid[axis], extent) <= bucket);
+            Some((axis, mid))
+        }
+        None => {
+            // Every centroid coincides, so no split can help; just halve big sets
+            if count <= MAX_LEAF_SIZE {
+                None
+            } else {
+                Some((centroid_bounds.longest_axis(), count / 2))
+            }
+        }
+    }
+}
+
+fn bucket_index(c: f64, extent: Interval) -> usize {
+    let b = ((c - extent.min) / extent.size() * SAH_BUCKETS as f64) as usize;
+    b.min(SAH_BUCKETS - 1)
+}
+
+// Moves the elements matching `pred` to the front and returns how many there are
+fn partition(info: &mut [PrimInfo], pred: impl Fn(&PrimInfo) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..info.len() {
+        if pred(&info[i]) {
+            info.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use crate::sphere::Sphere;
+    use crate::plane::Plane;
+    use crate::material::{Material, Lambertian};
+    use crate::color::Color;
+    use crate::vec3::Vec3;
+    use crate::util::random_f64_range;
+
+    fn random_scene() -> (HittableList, HittableList) {
+        let mut list = HittableList::new();
+        let mut copy = HittableList::new();
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0));
+        for _ in 0..500 {
+            let center = Vec3::random_range(-10.0, 10.0);
+            let radius = random_f64_range(0.05, 0.5);
+            list.add(Sphere::new(center, radius, Arc::clone(&mat)));
+            copy.add(Sphere::new(center, radius, Arc::clone(&mat)));
+        }
+        let ground = Point3::new(0.0, -11.0, 0.0);
+        list.add(Plane::new(ground, Vec3::new(0.0, 1.0, 0.0), Arc::clone(&mat)));
+        copy.add(Plane::new(ground, Vec3::new(0.0, 1.0, 0.0), mat));
+        (list, copy)
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        let (list, copy) = random_scene();
+        let bvh = BvhNode::new(copy);
+        for _ in 0..2000 {
+            let r = Ray::new(Vec3::random_range(-15.0, 15.0), Vec3::random_unit_vector());
+            let expected = list.hit(&r, Interval::new(0.001, f64::INFINITY)).map(|rec| rec.t);
+            let actual = bvh.hit(&r, Interval::new(0.001, f64::INFINITY)).map(|rec| rec.t);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_unbounded_objects() {
+        let (list, _) = random_scene();
+        assert!(list.bounding_box().is_none());
+        assert!(BvhNode::new(list).bounding_box().is_none());
+    }
+}
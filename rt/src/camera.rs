@@ -1,11 +1,13 @@
 use crate::hittable::Hittable;
-use crate::color::{Color, write_color};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
 use crate::ray::Ray;
-use crate::interval::Interval;
 use crate::vec3::{Vec3, Point3};
 use crate::util::random_f64;
 use crate::light::Light;
-use std::io::Write;
+use crate::spectrum;
+use crate::integrator::IntegratorKind;
+use crate::material::MaterialLibrary;
 use rayon::prelude::*;
 use indicatif::{ProgressBar, ProgressStyle};
 
@@ -21,6 +23,10 @@ pub struct Camera {
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    // Trace one wavelength per sample instead of RGB
+    pub spectral: bool,
+    // What each camera ray is shaded with
+    pub integrator: IntegratorKind,
 
     //private
     image_height: usize,
@@ -47,15 +53,14 @@ impl Camera{
             lookfrom: Point3::new(0.0, 0.0, 0.0),
             lookat: Point3::new(0.0, 0.0, -1.0),
             vup: Vec3::new(0.0, 1.0, 0.0),
+            focus_dist: 10.0,
             ..Default::default()
         }
     }
     
-    pub fn render(&mut self, world: &(impl Hittable + Sync), lights: &[Light]) {
+    pub fn render(&mut self, world: &impl Hittable, lights: &[Light], materials: &MaterialLibrary) -> Framebuffer {
         self.initialize();
-        let mut out = std::io::stdout();
-
-        writeln!(out, "P3\n{} {}\n255", self.image_width, self.image_height).unwrap();
+        let integrator = self.integrator.build(self, materials);
 
         // Create a vector of all pixel coordinates
         let total_pixels = self.image_width * self.image_height;
@@ -78,9 +83,11 @@ impl Camera{
             .par_iter()
             .map(|&(i, j)| {
                 let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                for _sample in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += self.ray_color(&r, self.max_depth, world, lights);
+                for sample in 0..self.samples_per_pixel {
+                    let r = self.get_ray(i, j, sample);
+                    let radiance = integrator.radiance(&r, world, lights);
+                    // A spectral sample only carries its own wavelength
+                    pixel_color += if self.spectral { spectrum::film_weight(r.wavelength) * radiance } else { radiance };
                 }
                 pb.inc(1);
                 self.pixel_samples_scale * pixel_color
@@ -89,16 +96,16 @@ impl Camera{
 
         pb.finish_with_message("Done!");
 
-        // Write pixels in order
-        for pixel_color in pixel_colors {
-            write_color(&mut out, pixel_color);
-        }
+        Framebuffer::new(self.image_width, self.image_height, pixel_colors)
+    }
+
+    pub fn image_height(&self) -> usize {
+        ((self.image_width as f64 / self.aspect_ratio) as usize).max(1)
     }
      
     fn initialize(&mut self){
          //calculate the image hight
-        self.image_height = (self.image_width as f64 / self.aspect_ratio) as usize;
-        self.image_height = if self.image_height < 1 {1} else {self.image_height};
+        self.image_height = self.image_height();
 
         self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
 
@@ -132,14 +139,19 @@ impl Camera{
         self.defocus_disk_v = defocus_radius * self.v;
     }
 
-    fn get_ray(&self, i: usize, j: usize) -> Ray {
+    // Sample `sample` of pixel (i, j). The pixel's samples split the visible
+    // spectrum between them, so each pixel sees every colour evenly.
+    fn get_ray(&self, i: usize, j: usize, sample: usize) -> Ray {
         let offset = self.sample_square();
         let pixel_sample = self.pixel00_loc 
             + (i as f64 + offset.x) * self.pixel_delta_u
             + (j as f64 + offset.y) * self.pixel_delta_v;
         let ray_origin = if self.defocus_angle <= 0.0 {self.center} else {self.defocus_disk_sample() };
         let ray_direction = pixel_sample - ray_origin;
-        Ray::new(ray_origin, ray_direction)
+        let mut r = Ray::new(ray_origin, ray_direction);
+        r.wavelength = spectrum::sample_wavelength((sample as f64 + random_f64()) * self.pixel_samples_scale);
+        r.monochromatic = self.spectral;
+        r
     }
 
     fn defocus_disk_sample(&self) -> Point3 {
@@ -150,99 +162,4 @@ impl Camera{
         Vec3::new(random_f64() - 0.5, random_f64() - 0.5, 0.0)
     }
 
-    
-    fn ray_color(&self, r: &Ray, depth: usize, world: &impl Hittable, lights: &[Light]) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0)
-        }
-        
-        if let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) {
-            // Add object's own emission (brightness)
-            let emission = rec.mat.emission();
-            
-            // Continue with material scattering
-            if let Some((attenuation, scattered)) = rec.mat.scatter(r, &rec) {
-                // Check if this is a transparent material (glass)
-                // Transparent materials have white attenuation and no emission
-                let is_transparent = (attenuation.r - 1.0).abs() < 0.001 &&
-                                     (attenuation.g - 1.0).abs() < 0.001 &&
-                                     (attenuation.b - 1.0).abs() < 0.001 &&
-                                     (emission.r + emission.g + emission.b) < 0.001;
-                
-                if is_transparent {
-                    // For transparent materials (glass), skip direct lighting
-                    // Only show refracted/reflected light
-                    let indirect_light = self.ray_color(&scattered, depth-1, world, lights);
-                    return attenuation * indirect_light;
-                } else {
-                    // For opaque materials, calculate direct lighting
-                    let mut direct_light = Color::new(0.0, 0.0, 0.0);
-                    
-                    for light in lights {
-                        // Offset point to avoid self-intersection
-                        let offset_point = rec.p + 0.001 * rec.normal;
-                        let light_dir = (light.position - offset_point).unit_vector();
-                        let distance_to_light = (light.position - offset_point).length();
-                        
-                        // Cast shadow ray
-                        let shadow_ray = Ray::new(offset_point, light_dir);
-                        let shadow_hit = world.hit(&shadow_ray, Interval::new(0.001, distance_to_light));
-                        
-                        let cos_theta = Vec3::dot(rec.normal, light_dir).max(0.0);
-                        
-                        if let Some(shadow_rec) = shadow_hit {
-                            // Object is blocking light - create shadow
-                            // All objects create the same shadow darkness (no direct light from this source)
-                            // The blocker's emission can add some light to the shadow area
-                            let blocker_emission = shadow_rec.mat.emission();
-                            let emission_strength = (blocker_emission.r + blocker_emission.g + blocker_emission.b) / 3.0;
-                            
-                            // Add a small amount of emission from the blocking object to the shadow area
-                            // This makes glowing objects cast slightly lighter shadows
-                            if emission_strength > 0.05 {
-                                direct_light = direct_light + 0.15 * blocker_emission;
-                            }
-                            // No direct light contribution from the light source (shadow)
-                        } else {
-                            // No occlusion - full light
-                            let light_contribution = cos_theta * light.intensity * light.color;
-                            direct_light = direct_light + light_contribution;
-                        }
-                    }
-                    
-                    let indirect_light = self.ray_color(&scattered, depth-1, world, lights);
-                    return emission + direct_light * attenuation + attenuation * indirect_light;
-                }
-            } else {
-                // Material doesn't scatter (shouldn't happen, but handle it)
-                // Calculate direct lighting for non-scattering materials
-                let mut direct_light = Color::new(0.0, 0.0, 0.0);
-                
-                for light in lights {
-                    // Offset point to avoid self-intersection
-                    let offset_point = rec.p + 0.001 * rec.normal;
-                    let light_dir = (light.position - offset_point).unit_vector();
-                    let distance_to_light = (light.position - offset_point).length();
-                    
-                    let shadow_ray = Ray::new(offset_point, light_dir);
-                    let shadow_hit = world.hit(&shadow_ray, Interval::new(0.001, distance_to_light));
-                    
-                    let cos_theta = Vec3::dot(rec.normal, light_dir).max(0.0);
-                    
-                    if shadow_hit.is_none() {
-                        let light_contribution = cos_theta * light.intensity * light.color;
-                        direct_light = direct_light + light_contribution;
-                    }
-                }
-                
-                return emission + direct_light;
-            }
-        }
-        
-        // Background color (sky)
-        let unit_direction = r.direction.unit_vector();
-        let a = 0.5 * (unit_direction.y + 1.0);
-        (1.0 - a)*Color::new(1.0, 1.0, 1.0) + a*Color::new(0.5, 0.7, 1.0)
-    }
-
 }
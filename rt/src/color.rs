@@ -59,7 +59,7 @@ impl AddAssign for Color {
 }
 use std::io::Write;
 
-const intensity: Interval = Interval::new(0.0, 0.999);
+const INTENSITY: Interval = Interval::new(0.0, 0.999);
 
 fn linear_to_gamma(linear_component: f64) -> f64 {
     if linear_component > 0.0 {
@@ -68,13 +68,18 @@ fn linear_to_gamma(linear_component: f64) -> f64 {
         0.0
     }
 }
-pub fn write_color(out: &mut impl Write, pixel_color: Color) {
+
+// Gamma 2 corrected, 8-bit quantised bytes for display formats
+pub fn to_bytes(pixel_color: Color) -> [u8; 3] {
     let r = linear_to_gamma(pixel_color.r);
     let g = linear_to_gamma(pixel_color.g);
     let b = linear_to_gamma(pixel_color.b);
-    let rbyte = (256.0 * intensity.clamp(r)) as usize;
-    let gbyte = (256.0 * intensity.clamp(g)) as usize;
-    let bbyte = (256.0 * intensity.clamp(b)) as usize;
+    [(256.0 * INTENSITY.clamp(r)) as u8,
+     (256.0 * INTENSITY.clamp(g)) as u8,
+     (256.0 * INTENSITY.clamp(b)) as u8]
+}
 
-    writeln!(out, "{rbyte} {gbyte} {bbyte}").unwrap();
+pub fn write_color(out: &mut impl Write, pixel_color: Color) -> std::io::Result<()> {
+    let [rbyte, gbyte, bbyte] = to_bytes(pixel_color);
+    writeln!(out, "{rbyte} {gbyte} {bbyte}")
 }
\ No newline at end of file
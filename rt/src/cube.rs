@@ -1,22 +1,25 @@
+use std::sync::Arc;
 use crate::vec3::{Point3, Vec3};
-use crate::hittable::{Hittable, HitRecord};
+use crate::hittable::{Hittable, HitRecord, LightSample};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::util::random_f64;
 
-pub struct Cube<'a> {
+pub struct Cube {
     pub min: Point3,  // Minimum corner (bottom-left-back)
     pub max: Point3,   // Maximum corner (top-right-front)
-    pub mat: Box<dyn Material + 'a>
+    pub mat: Arc<dyn Material>
 }
 
-impl <'a>Cube<'a> {
-    pub fn new(min: Point3, max: Point3, mat: impl Material + 'a) -> Cube<'a> {
-        Cube{ min, max, mat: Box::new(mat) }
+impl Cube {
+    pub fn new(min: Point3, max: Point3, mat: Arc<dyn Material>) -> Cube {
+        Cube{ min, max, mat }
     }
     
     // Helper function to create a cube from center and size
-    pub fn from_center_size(center: Point3, size: f64, mat: impl Material + 'a) -> Cube<'a> {
+    pub fn from_center_size(center: Point3, size: f64, mat: Arc<dyn Material>) -> Cube {
         let half = size / 2.0;
         Cube::new(
             Point3::new(center.x - half, center.y - half, center.z - half),
@@ -24,10 +27,16 @@ impl <'a>Cube<'a> {
             mat
         )
     }
+
+    // Area of one face facing along x, y and z
+    fn face_areas(&self) -> [f64; 3] {
+        let size = self.max - self.min;
+        [size.y * size.z, size.x * size.z, size.x * size.y]
+    }
 }
 
-impl <'a>Hittable for Cube<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+impl Hittable for Cube {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let mut t_min = ray_t.min;
         let mut t_max = ray_t.max;
         
@@ -76,12 +85,15 @@ impl <'a>Hittable for Cube<'a> {
             }
         }
         
-        // Check if intersection is within ray interval
-        if !ray_t.surrounds(t_min) {
+        // Check if intersection is within ray interval. From inside the
+        // cube (glass, say) the only hit is on the way out.
+        let t = if ray_t.surrounds(t_min) {
+            t_min
+        } else if ray_t.surrounds(t_max) {
+            t_max
+        } else {
             return None;
-        }
-        
-        let t = t_min;
+        };
         let p = r.at(t);
         
         // Calculate normal based on which face was hit
@@ -115,8 +127,44 @@ impl <'a>Hittable for Cube<'a> {
         }
         
         let mut rec = HitRecord::new(t, p, normal, &*self.mat);
+        // Each face maps its two in-plane axes onto [0, 1]
+        let axis = if normal.x != 0.0 { 0 } else if normal.y != 0.0 { 1 } else { 2 };
+        let along = |i: usize| (p[i] - self.min[i]) / (self.max[i] - self.min[i]);
+        (rec.u, rec.v) = (along((axis + 1) % 3), along((axis + 2) % 3));
         rec.set_face_normal(r);
         Some(rec)
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.min, self.max))
+    }
+
+    // Uniform over the surface: a face is picked in proportion to its area
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let size = self.max - self.min;
+        let areas = self.face_areas();
+        let face_total = areas[0] + areas[1] + areas[2];
+
+        let pick = random_f64() * face_total;
+        let axis = if pick < areas[0] { 0 } else if pick < areas[0] + areas[1] { 1 } else { 2 };
+        let on_max = random_f64() < 0.5;
+
+        let mut p = [0.0; 3];
+        let mut normal = [0.0; 3];
+        for (i, c) in p.iter_mut().enumerate() {
+            *c = self.min[i] + random_f64() * size[i];
+        }
+        p[axis] = if on_max { self.max[axis] } else { self.min[axis] };
+        normal[axis] = if on_max { 1.0 } else { -1.0 };
+
+        let p = Point3::new(p[0], p[1], p[2]);
+        let normal = Vec3::new(normal[0], normal[1], normal[2]);
+        LightSample::from_area(origin, p, normal, 2.0 * face_total)
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let [x, y, z] = self.face_areas();
+        LightSample::area_pdf(self, r, ray_t, 2.0 * (x + y + z))
+    }
 }
 
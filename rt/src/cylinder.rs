@@ -1,83 +1,221 @@
+use std::sync::Arc;
+use std::f64::consts::PI;
 use crate::vec3::{Point3, Vec3};
-use crate::hittable::{Hittable, HitRecord};
+use crate::hittable::{Hittable, HitRecord, LightSample};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::util::random_f64;
 
-pub struct Cylinder<'a> {
-    pub center: Point3,      // Center of the cylinder
+// Cylinder running from `base` to `top`, at any angle, optionally closed
+// with flat caps
+pub struct Cylinder {
+    pub base: Point3,
+    pub top: Point3,
     pub radius: f64,
-    pub height: f64,
-    pub mat: Box<dyn Material + 'a>
+    pub capped: bool,
+    pub mat: Arc<dyn Material>,
+    axis: Vec3,    // unit vector from base to top
+    height: f64,
+    // Perpendicular to the axis, where u starts and a quarter turn on
+    across: (Vec3, Vec3),
 }
 
-impl <'a>Cylinder<'a> {
-    pub fn new(center: Point3, radius: f64, height: f64, mat: impl Material + 'a) -> Cylinder<'a> {
-        Cylinder{ center, radius, height, mat: Box::new(mat) }
+impl Cylinder {
+    pub fn new(base: Point3, top: Point3, radius: f64, capped: bool, mat: Arc<dyn Material>) -> Cylinder {
+        let height = (top - base).length();
+        let axis = (top - base) / height;
+        Cylinder {
+            base,
+            top,
+            radius,
+            capped,
+            mat,
+            axis,
+            height,
+            across: Vec3::orthonormal_basis(axis),
+        }
+    }
+
+    // Helper function for an upright, capped cylinder centred on `center`
+    pub fn from_center_height(center: Point3, radius: f64, height: f64, mat: Arc<dyn Material>) -> Cylinder {
+        let half = Vec3::new(0.0, height / 2.0, 0.0);
+        Cylinder::new(center - half, center + half, radius, true, mat)
+    }
+
+    // Area of the curved side and of one cap
+    fn areas(&self) -> (f64, f64) {
+        let cap_area = if self.capped { PI * self.radius * self.radius } else { 0.0 };
+        (2.0 * PI * self.radius * self.height, cap_area)
     }
-}
 
-impl <'a>Hittable for Cylinder<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let y_min = self.center.y - self.height / 2.0;
-        let y_max = self.center.y + self.height / 2.0;
-        
-        let oc = Vec3::new(
-            self.center.x - r.origin.x,
-            0.0,  // y component not used in quadratic
-            self.center.z - r.origin.z
-        );
-        let a = r.direction.x * r.direction.x + r.direction.z * r.direction.z;
-        if a < 1e-8 {  // Ray is parallel to cylinder axis
+    // Angle around the axis as a fraction of a turn, in [0, 1]
+    fn turn(&self, radial: Vec3) -> f64 {
+        let angle = Vec3::dot(radial, self.across.1).atan2(Vec3::dot(radial, self.across.0));
+        angle / (2.0 * PI) + 0.5
+    }
+
+    // Nearest hit on the curved side
+    fn hit_side(&self, r: &Ray, ray_t: Interval) -> Option<(f64, Vec3, (f64, f64))> {
+        // Work with the parts of the ray perpendicular to the axis; what's
+        // left is a 2D ray against a circle
+        let oc = r.origin - self.base;
+        let d_along = Vec3::dot(r.direction, self.axis);
+        let oc_along = Vec3::dot(oc, self.axis);
+        let d_perp = r.direction - d_along * self.axis;
+        let oc_perp = oc - oc_along * self.axis;
+
+        let a = d_perp.length_squared();
+        if a < 1e-12 {
+            // Parallel to the axis: the side can only be grazed, never entered
             return None;
         }
-        let h = r.direction.x * oc.x + r.direction.z * oc.z;
-        let c = oc.x * oc.x + oc.z * oc.z - self.radius * self.radius;
-        let discriminant = h*h - a*c;
+        let h = Vec3::dot(d_perp, oc_perp);
+        let c = oc_perp.length_squared() - self.radius * self.radius;
+        let discriminant = h * h - a * c;
         if discriminant < 0.0 {
             return None;
         }
         let sqrtd = discriminant.sqrt();
-        let mut root = (h - sqrtd) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrtd) / a;
-            if !ray_t.surrounds(root) {
-                return None;
+        for t in [(-h - sqrtd) / a, (-h + sqrtd) / a] {
+            let along = oc_along + t * d_along;
+            if ray_t.surrounds(t) && (0.0..=self.height).contains(&along) {
+                let radial = oc_perp + t * d_perp;
+                let normal = radial / self.radius;
+                return Some((t, normal, (self.turn(radial), along / self.height)));
             }
         }
-        let p = r.at(root);
-        if p.y < y_min || p.y > y_max {
-            // Try the other root
-            root = (h + sqrtd) / a;
-            if !ray_t.surrounds(root) {
-                return None;
+        None
+    }
+
+    // Nearest hit on either cap
+    fn hit_caps(&self, r: &Ray, ray_t: Interval) -> Option<(f64, Vec3, (f64, f64))> {
+        let d_along = Vec3::dot(r.direction, self.axis);
+        if !self.capped || d_along.abs() < 1e-12 {
+            return None;
+        }
+        let mut nearest: Option<(f64, Vec3, (f64, f64))> = None;
+        for (center, normal) in [(self.base, -self.axis), (self.top, self.axis)] {
+            let t = Vec3::dot(center - r.origin, self.axis) / d_along;
+            if !ray_t.surrounds(t) || nearest.is_some_and(|(best, _, _)| best <= t) {
+                continue;
             }
-            let p2 = r.at(root);
-            if p2.y < y_min || p2.y > y_max {
-                return None;
+            let radial = r.at(t) - center;
+            let dist_squared = radial.length_squared();
+            if dist_squared <= self.radius * self.radius {
+                // Polar like a Disk: angle around the axis, distance from the centre
+                nearest = Some((t, normal, (self.turn(radial), dist_squared.sqrt() / self.radius)));
             }
-            // Use second root
-            let t = root;
-            let p = p2;
-            let normal = Vec3::new(
-                (p.x - self.center.x) / self.radius,
-                0.0,
-                (p.z - self.center.z) / self.radius
-            );
-            let mut rec = HitRecord::new(t, p, normal, &*self.mat);
-            rec.set_face_normal(r);
-            Some(rec)
-        } else {
-            // First root is valid
-            let t = root;
-            let normal = Vec3::new(
-                (p.x - self.center.x) / self.radius,
-                0.0,
-                (p.z - self.center.z) / self.radius
-            );
-            let mut rec = HitRecord::new(t, p, normal, &*self.mat);
-            rec.set_face_normal(r);
-            Some(rec)
         }
+        nearest
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let side = self.hit_side(r, ray_t);
+        // Only caps closer than the side hit matter
+        let cap_t = Interval::new(ray_t.min, side.map_or(ray_t.max, |(t, _, _)| t));
+        let (t, normal, (u, v)) = self.hit_caps(r, cap_t).or(side)?;
+
+        let mut rec = HitRecord::new(t, r.at(t), normal, &*self.mat);
+        (rec.u, rec.v) = (u, v);
+        rec.set_face_normal(r);
+        Some(rec)
+    }
+
+    // The end disks reach radius * sin(angle between axis and each world axis)
+    fn bounding_box(&self) -> Option<Aabb> {
+        let a = self.axis;
+        let extent = |c: f64| self.radius * (1.0 - c * c).max(0.0).sqrt();
+        let e = Vec3::new(extent(a.x), extent(a.y), extent(a.z));
+        let base = Aabb::from_points(self.base - e, self.base + e);
+        Some(Aabb::surrounding(&base, &Aabb::from_points(self.top - e, self.top + e)))
+    }
+
+    // Uniform over the surface that can be hit: the side plus any caps
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let (side_area, cap_area) = self.areas();
+        let total = side_area + 2.0 * cap_area;
+
+        let (u, v) = self.across;
+        let phi = 2.0 * PI * random_f64();
+        let around = phi.cos() * u + phi.sin() * v;
+        let pick = random_f64() * total;
+        let (p, normal) = if pick < side_area {
+            (self.base + random_f64() * self.height * self.axis + self.radius * around, around)
+        } else {
+            let r = self.radius * random_f64().sqrt();
+            if pick < side_area + cap_area {
+                (self.base + r * around, -self.axis)
+            } else {
+                (self.top + r * around, self.axis)
+            }
+        };
+        LightSample::from_area(origin, p, normal, total)
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let (side_area, cap_area) = self.areas();
+        LightSample::area_pdf(self, r, ray_t, side_area + 2.0 * cap_area)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+
+    fn grey() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0))
+    }
+
+    fn hit(cyl: &Cylinder, origin: Point3, direction: Vec3) -> Option<(f64, Vec3)> {
+        cyl.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY)).map(|rec| (rec.t, rec.normal))
     }
-}
\ No newline at end of file
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_rays_along_the_axis() {
+        // Lying along x from 0 to 4
+        let capped = Cylinder::new(Point3::new(0.0, 0.0, 0.0), Point3::new(4.0, 0.0, 0.0), 1.0, true, grey());
+        let (t, normal) = hit(&capped, Point3::new(-2.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((t - 2.0).abs() < 1e-9 && close(normal, Vec3::new(-1.0, 0.0, 0.0)));
+        let (t, normal) = hit(&capped, Point3::new(6.0, 0.0, 0.5), Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((t - 2.0).abs() < 1e-9 && close(normal, Vec3::new(1.0, 0.0, 0.0)));
+
+        // Without caps the same ray goes straight through, and one outside misses
+        let open = Cylinder::new(Point3::new(0.0, 0.0, 0.0), Point3::new(4.0, 0.0, 0.0), 1.0, false, grey());
+        assert!(hit(&open, Point3::new(-2.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&capped, Point3::new(-2.0, 1.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+
+        // From inside an open tube the far wall is seen from the back
+        let rec = open.hit(&Ray::new(Point3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(!rec.front_face && close(rec.normal, Vec3::new(0.0, -1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_tilted_side_and_bounds() {
+        let base = Point3::new(1.0, 0.0, 0.0);
+        let top = Point3::new(1.0, 3.0, 3.0);
+        let cyl = Cylinder::new(base, top, 0.5, true, grey());
+        let axis = (top - base).unit_vector();
+
+        // Straight at the middle of the side, from along +x
+        let middle = base + 0.5 * (top - base);
+        let (t, normal) = hit(&cyl, middle + Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((t - 4.5).abs() < 1e-9);
+        assert!(close(normal, Vec3::new(1.0, 0.0, 0.0)));
+        assert!(Vec3::dot(normal, axis).abs() < 1e-9);
+
+        let b = cyl.bounding_box().unwrap();
+        assert!((b.x.min - 0.5).abs() < 1e-9 && (b.x.max - 1.5).abs() < 1e-9);
+        let reach = 0.5 * (0.5f64).sqrt();
+        assert!((b.y.min + reach).abs() < 1e-9 && (b.z.max - 3.0 - reach).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::f64::consts::PI;
+use crate::vec3::{Point3, Vec3};
+use crate::hittable::{Hittable, HitRecord, LightSample};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::util::random_f64;
+
+pub struct Disk {
+    pub center: Point3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub mat: Arc<dyn Material>,
+    axes: (Vec3, Vec3),
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Material>) -> Disk {
+        let normal = normal.unit_vector();
+        Disk { center, normal, radius, mat, axes: Vec3::orthonormal_basis(normal) }
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = Vec3::dot(self.normal, r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = Vec3::dot(self.center - r.origin, self.normal) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+        let p = r.at(t);
+        let offset = p - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+        let mut rec = HitRecord::new(t, p, self.normal, &*self.mat);
+        // Polar: u is the angle around the centre, v the distance from it
+        let angle = Vec3::dot(offset, self.axes.1).atan2(Vec3::dot(offset, self.axes.0));
+        (rec.u, rec.v) = (angle / (2.0 * PI) + 0.5, offset.length() / self.radius);
+        rec.set_face_normal(r);
+        Some(rec)
+    }
+
+    // The disk reaches radius * sin(angle between normal and axis) along each axis
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.normal;
+        let extent = |c: f64| self.radius * (1.0 - c * c).max(0.0).sqrt();
+        let e = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+        Some(Aabb::from_points(self.center - e, self.center + e))
+    }
+
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let (u, v) = self.axes;
+        let r = self.radius * random_f64().sqrt();
+        let phi = 2.0 * PI * random_f64();
+        let p = self.center + r * phi.cos() * u + r * phi.sin() * v;
+        LightSample::from_area(origin, p, self.normal, self.area())
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        LightSample::area_pdf(self, r, ray_t, self.area())
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+use crate::color::{Color, to_bytes, write_color};
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ImageFormat {
+    P3,  // ASCII PPM, one pixel per line
+    P6,  // binary PPM
+    Png,
+    Pfm,  // linear, unclamped 32-bit float
+}
+
+impl ImageFormat {
+    // Guess from a file extension, e.g. "png"
+    pub fn from_extension(ext: &str) -> Option<ImageFormat> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::P3),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+// Linear, unclamped pixel colors in row-major order, top row first
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Framebuffer {
+        assert_eq!(pixels.len(), width * height, "pixel count doesn't match {width}x{height}");
+        Framebuffer { width, height, pixels }
+    }
+
+    pub fn write(&self, out: &mut impl Write, format: ImageFormat) -> std::io::Result<()> {
+        // Checked here so every format fails the same way
+        if self.width == 0 || self.height == 0 {
+            let message = format!("can't write an empty {}x{} image", self.width, self.height);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+        }
+        match format {
+            ImageFormat::P3 => self.write_p3(out)?,
+            ImageFormat::P6 => self.write_p6(out)?,
+            ImageFormat::Png => self.write_png(out)?,
+            ImageFormat::Pfm => self.write_pfm(out)?,
+        }
+        out.flush()
+    }
+
+    fn rgb8(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|&c| to_bytes(c)).collect()
+    }
+
+    fn write_p3(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "P3\n{} {}\n255", self.width, self.height)?;
+        for &pixel_color in &self.pixels {
+            write_color(out, pixel_color)?;
+        }
+        Ok(())
+    }
+
+    fn write_p6(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.rgb8())
+    }
+
+    fn write_png(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        // to_bytes encodes with a gamma of 2 (square root), so the file gamma is 1/2
+        encoder.set_source_gamma(png::ScaledFloat::new(0.5));
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb8())?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    // Portable float map: raw linear values with no gamma or clamping, for
+    // tone mapping and compositing later. A negative scale marks little-endian
+    // data, and rows are stored bottom to top.
+    fn write_pfm(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        let mut data = Vec::with_capacity(self.pixels.len() * 12);
+        for row in self.pixels.chunks(self.width).rev() {
+            for c in row {
+                for v in [c.r, c.g, c.b] {
+                    data.extend_from_slice(&(v as f32).to_le_bytes());
+                }
+            }
+        }
+        out.write_all(&data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient() -> Framebuffer {
+        let pixels = (0..6).map(|i| Color::new(i as f64 / 5.0, 0.25, 4.0)).collect();
+        Framebuffer::new(3, 2, pixels)
+    }
+
+    #[test]
+    fn test_ppm_encoders_agree() {
+        let image = gradient();
+        let mut p3 = Vec::new();
+        let mut p6 = Vec::new();
+        image.write(&mut p3, ImageFormat::P3).unwrap();
+        image.write(&mut p6, ImageFormat::P6).unwrap();
+
+        let p3 = String::from_utf8(p3).unwrap();
+        let p3_values: Vec<u8> = p3.split_whitespace().skip(4).map(|v| v.parse().unwrap()).collect();
+        assert!(p6.starts_with(b"P6\n3 2\n255\n"));
+        assert_eq!(&p6[11..], &p3_values[..]);
+        // 0.25 linear is 0.5 after gamma; values above 1 clamp to 255
+        assert_eq!(&p3_values[..3], &[0, 128, 255]);
+    }
+
+    #[test]
+    fn test_pfm_keeps_linear_values() {
+        let image = gradient();
+        let mut data = Vec::new();
+        image.write(&mut data, ImageFormat::Pfm).unwrap();
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert!(data.starts_with(header));
+        let floats: Vec<f32> = data[header.len()..].chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats.len(), 18);
+        // Bottom row (pixels 3..6) comes first, and 4.0 survives unclamped
+        assert_eq!(&floats[..3], &[0.6, 0.25, 4.0]);
+        assert_eq!(&floats[9..12], &[0.0, 0.25, 4.0]);
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let image = gradient();
+        let mut data = Vec::new();
+        image.write(&mut data, ImageFormat::Png).unwrap();
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(data)).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert_eq!(&buf[..info.buffer_size()], &image.rgb8()[..]);
+    }
+
+    #[test]
+    fn test_empty_image_is_an_error() {
+        let empty = Framebuffer::new(0, 4, Vec::new());
+        for format in [ImageFormat::P3, ImageFormat::P6, ImageFormat::Png, ImageFormat::Pfm] {
+            let err = empty.write(&mut Vec::new(), format).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -2,6 +2,8 @@ use crate::vec3::{Point3, Vec3};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use std::sync::Arc;
 
 pub struct HitRecord<'a> {
     pub t: f64,
@@ -9,11 +11,14 @@ pub struct HitRecord<'a> {
     pub normal: Vec3,
     pub front_face: bool,
     pub mat: &'a dyn Material,
+    // Surface coordinates for textures, set by the primitive
+    pub u: f64,
+    pub v: f64,
 }
 
 impl <'a>HitRecord<'a> {
     pub fn new(t: f64, p: Point3, normal: Vec3, mat: &'a dyn Material) -> HitRecord<'a>{
-        HitRecord {p, normal, t, front_face: true, mat}
+        HitRecord {p, normal, t, front_face: true, mat, u: 0.0, v: 0.0}
     }
     
     pub fn set_face_normal(&mut self, r: &Ray) {
@@ -24,6 +29,134 @@ impl <'a>HitRecord<'a> {
     }
 }
 
-pub trait Hittable: Sync {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
-}
\ No newline at end of file
+pub trait Hittable: Send + Sync {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>>;
+    // Axis-aligned box enclosing every point the object can be hit at.
+    // Return None for objects that extend forever (e.g. Plane); containers
+    // holding such an object are unbounded too.
+    fn bounding_box(&self) -> Option<Aabb>;
+    // Picks a point on the surface for direct lighting from `origin`. Objects
+    // that can't be sampled (e.g. Plane) keep the default and never act as lights.
+    fn sample(&self, _origin: Point3) -> Option<LightSample> {
+        None
+    }
+    // Density per unit solid angle with which `sample(r.origin)` picks the
+    // first point `r` hits within `ray_t`; zero if it hits nothing there
+    fn sample_pdf(&self, _r: &Ray, _ray_t: Interval) -> f64 {
+        0.0
+    }
+}
+
+// Lets emissive objects be shared between the world and the light list
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        (**self).hit(r, ray_t)
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        (**self).sample(origin)
+    }
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        (**self).sample_pdf(r, ray_t)
+    }
+}
+
+// A point on a light together with its probability density per unit solid
+// angle as seen from the point being lit
+pub struct LightSample {
+    pub p: Point3,
+    pub pdf: f64,
+}
+
+impl LightSample {
+    // For points picked uniformly over a surface of the given area
+    pub fn from_area(origin: Point3, p: Point3, normal: Vec3, area: f64) -> Option<LightSample> {
+        let d = p - origin;
+        let dist_squared = d.length_squared();
+        let cosine = Vec3::dot(normal, d).abs() / dist_squared.sqrt();
+        if cosine < 1e-8 || area <= 0.0 {
+            return None;
+        }
+        Some(LightSample { p, pdf: dist_squared / (cosine * area) })
+    }
+
+    // `sample_pdf` for objects sampled uniformly over a surface of the given area
+    pub fn area_pdf(object: &impl Hittable, r: &Ray, ray_t: Interval, area: f64) -> f64 {
+        object.hit(r, ray_t)
+            .and_then(|rec| LightSample::from_area(r.origin, rec.p, rec.normal, area))
+            .map_or(0.0, |s| s.pdf)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::PI;
+    use crate::disk::Disk;
+    use crate::sphere::Sphere;
+    use crate::cube::Cube;
+    use crate::material::DiffuseLight;
+    use crate::color::Color;
+
+    // Monte Carlo estimate of the cosine-weighted solid angle the light covers
+    // as seen from a point at the origin facing +y
+    fn projected_solid_angle(light: &impl Hittable) -> f64 {
+        let n = 200_000;
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let s = light.sample(origin).unwrap();
+            let cosine = (s.p - origin).unit_vector().y.max(0.0);
+            sum += cosine / s.pdf;
+        }
+        sum / n as f64
+    }
+
+    #[test]
+    fn test_light_sampling_matches_irradiance() {
+        let light = || Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0), 1.0));
+
+        // Disk of radius r facing down from height h: pi r^2 / (r^2 + h^2)
+        let disk = Disk::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 1.5, light());
+        let expected = PI * 1.5 * 1.5 / (1.5 * 1.5 + 2.0 * 2.0);
+        assert!((projected_solid_angle(&disk) - expected).abs() < 0.01 * expected);
+
+        // Sphere straight overhead: pi sin^2(theta_max) = pi r^2 / d^2
+        let sphere = Sphere::new(Point3::new(0.0, 3.0, 0.0), 1.0, light());
+        let expected = PI / 9.0;
+        assert!((projected_solid_angle(&sphere) - expected).abs() < 0.01 * expected);
+    }
+
+    #[test]
+    fn test_sample_pdf_matches_sample() {
+        // Aiming at a sampled point gives back the density it was sampled
+        // with, whenever that point is the first one hit
+        let light = || Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0), 1.0));
+        let shapes: [(Box<dyn Hittable>, Point3); 4] = [
+            (Box::new(Disk::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 1.5, light())), Point3::zero()),
+            (Box::new(Sphere::new(Point3::new(0.0, 3.0, 0.0), 1.0, light())), Point3::zero()),
+            (Box::new(Sphere::new(Point3::new(0.0, 3.0, 0.0), 1.0, light())), Point3::new(0.2, 3.1, 0.0)),
+            (Box::new(Cube::new(Point3::new(1.0, 1.0, 1.0), Point3::new(2.0, 3.0, 4.0), light())), Point3::zero()),
+        ];
+        for (shape, origin) in &shapes {
+            let mut matched = 0;
+            for _ in 0..1000 {
+                let s = shape.sample(*origin).unwrap();
+                let r = Ray::new(*origin, s.p - *origin);
+                let ray_t = Interval::new(0.001, 1.0 + 1e-6);
+                if shape.hit(&r, Interval::new(0.001, 1.0 - 1e-6)).is_some() {
+                    continue;
+                }
+                assert!((shape.sample_pdf(&r, ray_t) - s.pdf).abs() < 1e-6 * s.pdf);
+                matched += 1;
+            }
+            assert!(matched > 100);
+        }
+
+        // Nothing is picked in directions that miss
+        let (disk, origin) = &shapes[0];
+        let away = Ray::new(*origin, Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(disk.sample_pdf(&away, Interval::new(0.001, f64::INFINITY)), 0.0);
+    }
+}
@@ -1,22 +1,29 @@
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
+use crate::aabb::Aabb;
 
-pub struct HittableList<'a> {
-    objects: Vec<Box<dyn Hittable + 'a>>
+pub struct HittableList {
+    objects: Vec<Box<dyn Hittable>>
 }
 
-impl<'a> HittableList<'a> {
-    pub fn new() -> HittableList<'a> {
+impl HittableList {
+    pub fn new() -> HittableList {
         HittableList { objects: Vec::new() }
     }
-    pub fn add(&mut self, object: impl Hittable + 'a) {
+    pub fn add(&mut self, object: impl Hittable + 'static) {
         self.objects.push(Box::new(object))
     }
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
+    }
 }
 
-impl<'a> Hittable for HittableList<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+impl Hittable for HittableList {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let mut rec = None;
         let mut closest_so_far = ray_t.max;
         for object in self.objects.iter() {
@@ -27,4 +34,11 @@ impl<'a> Hittable for HittableList<'a> {
         }
         rec
     }
-}
\ No newline at end of file
+
+    // Unbounded as soon as any member is unbounded
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.objects.iter().try_fold(Aabb::EMPTY, |bbox, object| {
+            object.bounding_box().map(|b| Aabb::surrounding(&bbox, &b))
+        })
+    }
+}
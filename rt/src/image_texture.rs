@@ -0,0 +1,279 @@
+use std::fmt;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::color::Color;
+use crate::texture::Texture;
+use crate::vec3::Point3;
+
+// What happens to uvs outside [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,   // the edge texels stretch outwards
+    Mirror,  // every other repeat is flipped
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io { path: PathBuf, err: std::io::Error },
+    Decode { path: PathBuf, message: String },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io { path, err } => write!(f, "{}: {err}", path.display()),
+            ImageError::Decode { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+// A picture mapped over uv space, (0, 0) at the bottom left. Texels are
+// stored as linear colors so filtering and shading work on real intensities.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,  // row-major, top row first
+    wrap: WrapMode,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, texels: Vec<Color>, wrap: WrapMode, filter: Filter) -> ImageTexture {
+        assert!(width > 0 && height > 0, "image texture can't be empty");
+        assert_eq!(texels.len(), width * height, "texel count doesn't match {width}x{height}");
+        ImageTexture { width, height, texels, wrap, filter }
+    }
+
+    // Reads a PNG or PPM (P3/P6) file, decoding its sRGB values to linear
+    pub fn load(path: &Path, wrap: WrapMode, filter: Filter) -> Result<ImageTexture, ImageError> {
+        let data = std::fs::read(path).map_err(|err| ImageError::Io { path: path.to_path_buf(), err })?;
+        let decoded = if data.starts_with(b"\x89PNG") {
+            decode_png(&data)
+        } else if data.starts_with(b"P3") || data.starts_with(b"P6") {
+            decode_ppm(&data)
+        } else {
+            Err("not a PNG or PPM (P3/P6) file".to_string())
+        };
+        let (width, height, texels) = decoded.map_err(|message| ImageError::Decode { path: path.to_path_buf(), message })?;
+        Ok(ImageTexture::new(width, height, texels, wrap, filter))
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let i = wrap(i, self.width, self.wrap);
+        let j = wrap(j, self.height, self.wrap);
+        self.texels[j * self.width + i]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        // Continuous texel coordinates, y going down the image
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Blend the four texels whose centres surround the point
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (i, j) = (x.floor() as i64, y.floor() as i64);
+                let (fx, fy) = (x - x.floor(), y - y.floor());
+                let top = (1.0 - fx) * self.texel(i, j) + fx * self.texel(i + 1, j);
+                let bottom = (1.0 - fx) * self.texel(i, j + 1) + fx * self.texel(i + 1, j + 1);
+                (1.0 - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+fn wrap(i: i64, n: usize, mode: WrapMode) -> usize {
+    let n = n as i64;
+    let i = match mode {
+        WrapMode::Repeat => i.rem_euclid(n),
+        WrapMode::Clamp => i.clamp(0, n - 1),
+        WrapMode::Mirror => {
+            let m = i.rem_euclid(2 * n);
+            if m < n { m } else { 2 * n - 1 - m }
+        }
+    };
+    i as usize
+}
+
+// The sRGB transfer curve, from an encoded value in [0, 1] to linear light
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+type Decoded = (usize, usize, Vec<Color>);
+
+fn decode_png(data: &[u8]) -> Result<Decoded, String> {
+    let mut decoder = png::Decoder::new(BufReader::new(Cursor::new(data)));
+    // Palettes are expanded and 16-bit samples cut to 8 bits
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let size = reader.output_buffer_size().ok_or("image is too large")?;
+    let mut buf = vec![0; size];
+    let info = reader.next_frame(&mut buf).map_err(|err| err.to_string())?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("indexed color was not expanded".to_string()),
+    };
+    let lut: Vec<f64> = (0..256).map(|v| srgb_to_linear(v as f64 / 255.0)).collect();
+    let (width, height) = (info.width as usize, info.height as usize);
+    // Alpha is ignored
+    let texels = buf[..info.buffer_size()]
+        .chunks(info.line_size)
+        .flat_map(|row| row[..width * channels].chunks(channels))
+        .map(|px| match channels {
+            1 | 2 => Color::new(lut[px[0] as usize], lut[px[0] as usize], lut[px[0] as usize]),
+            _ => Color::new(lut[px[0] as usize], lut[px[1] as usize], lut[px[2] as usize]),
+        })
+        .collect();
+    Ok((width, height, texels))
+}
+
+fn decode_ppm(data: &[u8]) -> Result<Decoded, String> {
+    // Header: magic, width, height and maximum value, separated by whitespace
+    // and possibly comments
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("truncated PPM header".to_string());
+        }
+        header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+    }
+    let number = |s: &str, what: &str| -> Result<usize, String> {
+        s.parse().map_err(|_| format!("invalid PPM {what} `{s}`"))
+    };
+    let width = number(&header[1], "width")?;
+    let height = number(&header[2], "height")?;
+    let max = number(&header[3], "maximum value")?;
+    if width == 0 || height == 0 {
+        return Err(format!("PPM image is {width}x{height}"));
+    }
+    if max == 0 || max > 65535 {
+        return Err(format!("PPM maximum value {max} is out of range"));
+    }
+
+    let count = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or("PPM image is too large")?;
+    let samples: Vec<usize> = if header[0] == "P6" {
+        // Exactly one whitespace byte separates the header from the raster
+        let raster = data.get(pos + 1..).unwrap_or(&[]);
+        let bytes = if max < 256 { 1 } else { 2 };
+        if raster.len() < count * bytes {
+            return Err(format!("PPM data ends after {} of {count} samples", raster.len() / bytes));
+        }
+        raster.chunks(bytes).take(count)
+            .map(|b| if bytes == 1 { b[0] as usize } else { (b[0] as usize) << 8 | b[1] as usize })
+            .collect()
+    } else {
+        let text = String::from_utf8_lossy(&data[pos..]);
+        let samples: Vec<usize> = text.split_whitespace().take(count)
+            .map(|s| number(s, "sample"))
+            .collect::<Result<_, _>>()?;
+        if samples.len() < count {
+            return Err(format!("PPM data ends after {} of {count} samples", samples.len()));
+        }
+        samples
+    };
+
+    let channel = |s: usize| srgb_to_linear(s.min(max) as f64 / max as f64);
+    let texels = samples.chunks(3).map(|s| Color::new(channel(s[0]), channel(s[1]), channel(s[2]))).collect();
+    Ok((width, height, texels))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn grey(v: f64) -> Color {
+        Color::new(v, v, v)
+    }
+
+    #[test]
+    fn test_wrap_and_filter() {
+        // 2x1: black on the left, white on the right
+        let image = |wrap, filter| ImageTexture::new(2, 1, vec![grey(0.0), grey(1.0)], wrap, filter);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        let nearest = image(WrapMode::Repeat, Filter::Nearest);
+        assert_eq!(nearest.value(0.2, 0.5, origin).r, 0.0);
+        assert_eq!(nearest.value(1.7, 0.5, origin).r, 1.0);
+
+        // Halfway between the two texel centres
+        let bilinear = image(WrapMode::Clamp, Filter::Bilinear);
+        assert!((bilinear.value(0.5, 0.5, origin).r - 0.5).abs() < 1e-12);
+        assert_eq!(bilinear.value(-3.0, 0.5, origin).r, 0.0);
+        assert_eq!(bilinear.value(3.0, 0.5, origin).r, 1.0);
+
+        assert_eq!((0..6).map(|i| wrap(i, 2, WrapMode::Mirror)).collect::<Vec<_>>(), [0, 1, 1, 0, 0, 1]);
+        assert_eq!(wrap(-1, 2, WrapMode::Mirror), 0);
+        assert_eq!(wrap(-1, 2, WrapMode::Repeat), 1);
+    }
+
+    #[test]
+    fn test_decode_ppm() {
+        let (w, h, texels) = decode_ppm(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 188\n").unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(texels[0].r, 1.0);
+        // sRGB 188 is about half the linear intensity
+        assert!((texels[1].b - 0.5).abs() < 0.01);
+
+        let (_, _, binary) = decode_ppm(b"P6 2 1 255\n\xff\x00\x00\x00\x00\xbc").unwrap();
+        assert_eq!(binary[1].b, texels[1].b);
+
+        assert!(decode_ppm(b"P6 2 1 255\n\xff\x00").is_err());
+        assert!(decode_ppm(b"P3 2 x 255\n").is_err());
+        assert!(decode_ppm(b"P3 2").is_err());
+    }
+
+    #[test]
+    fn test_load_png() {
+        let image = crate::framebuffer::Framebuffer::new(1, 1, vec![Color::new(0.25, 1.0, 0.0)]);
+        let mut data = Vec::new();
+        image.write(&mut data, crate::framebuffer::ImageFormat::Png).unwrap();
+        let (w, h, texels) = decode_png(&data).unwrap();
+        assert_eq!((w, h), (1, 1));
+        // Written as 128 (square-root gamma); read back as sRGB
+        assert!((texels[0].r - srgb_to_linear(128.0 / 255.0)).abs() < 1e-12);
+        assert_eq!(texels[0].g, 1.0);
+
+        let missing = ImageTexture::load(Path::new("no/such/texture.png"), WrapMode::Repeat, Filter::Bilinear);
+        assert!(matches!(missing, Err(ImageError::Io { .. })));
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use glam::DMat4;
+use crate::vec3::Point3;
+use crate::hittable::{Hittable, HitRecord, LightSample};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::transform::Transform;
+
+// One placement of shared geometry. Any number of instances can point at the
+// same object (a whole mesh, say) without copying it, each with its own
+// transform and optionally its own material.
+pub struct Instance {
+    placed: Transform,
+    mat: Option<Arc<dyn Material>>,
+}
+
+impl Instance {
+    pub fn new(geometry: Arc<dyn Hittable>, matrix: DMat4) -> Instance {
+        Instance { placed: Transform::new(geometry, matrix), mat: None }
+    }
+
+    // Drawn with `mat` instead of the geometry's own materials
+    pub fn with_material(geometry: Arc<dyn Hittable>, matrix: DMat4, mat: Arc<dyn Material>) -> Instance {
+        Instance { placed: Transform::new(geometry, matrix), mat: Some(mat) }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = self.placed.hit(r, ray_t)?;
+        if let Some(mat) = &self.mat {
+            rec.mat = &**mat;
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.placed.bounding_box()
+    }
+
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        self.placed.sample(origin)
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        self.placed.sample_pdf(r, ray_t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bvh::BvhNode;
+    use crate::hittable_list::HittableList;
+    use crate::transform::translate;
+    use crate::triangle::{MeshData, TriangleMesh};
+    use crate::material::{Lambertian, DiffuseLight};
+    use crate::color::Color;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn test_instances_share_geometry() {
+        // One triangle mesh placed along x five times, the last one glowing
+        let data = MeshData {
+            positions: vec![Point3::new(-0.5, 0.0, 0.0), Point3::new(0.5, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)],
+            indices: vec![[0, 1, 2]],
+            ..Default::default()
+        };
+        let mesh: Arc<dyn Hittable> = Arc::new(TriangleMesh::new(data, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0))));
+        let mut list = HittableList::new();
+        for i in 0..4 {
+            list.add(Instance::new(Arc::clone(&mesh), translate(Vec3::new(2.0 * i as f64, 0.0, 0.0))));
+        }
+        list.add(Instance::with_material(Arc::clone(&mesh), translate(Vec3::new(8.0, 0.0, 0.0)), Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0), 1.0))));
+        assert_eq!(Arc::strong_count(&mesh), 6);
+        let bvh = BvhNode::new(list);
+
+        for i in 0..5 {
+            let r = Ray::new(Point3::new(2.0 * i as f64, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = bvh.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            assert!((rec.p.x - 2.0 * i as f64).abs() < 1e-9);
+            assert_eq!(rec.mat.is_emissive(), i == 4);
+        }
+        let between = Ray::new(Point3::new(1.0, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(bvh.hit(&between, Interval::new(0.001, f64::INFINITY)).is_none());
+    }
+}
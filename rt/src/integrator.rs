@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use serde::Deserialize;
+use crate::hittable::{Hittable, HitRecord};
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::vec3::Vec3;
+use crate::util::random_f64;
+use crate::light::Light;
+use crate::material::{Material, MaterialLibrary};
+use crate::camera::Camera;
+
+// Bounces every path gets before Russian roulette may end it
+const ROULETTE_DEPTH: usize = 3;
+
+// Works out what the camera sees along one ray. `Camera::render` only picks
+// the rays and averages what comes back.
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> Color;
+}
+
+// The built-in integrators, by the name used in scene files and on the
+// command line
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegratorKind {
+    Whitted,
+    #[default]
+    Path,
+    Ao,
+    Normals,
+    Depth,
+    Uv,
+    Material,
+}
+
+impl IntegratorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IntegratorKind::Whitted => "whitted",
+            IntegratorKind::Path => "path",
+            IntegratorKind::Ao => "ao",
+            IntegratorKind::Normals => "normals",
+            IntegratorKind::Depth => "depth",
+            IntegratorKind::Uv => "uv",
+            IntegratorKind::Material => "material",
+        }
+    }
+
+    // Distances in the views that need one are relative to how far the
+    // camera is from what it looks at
+    pub fn build(self, cam: &Camera, materials: &MaterialLibrary) -> Box<dyn Integrator> {
+        let distance = (cam.lookat - cam.lookfrom).length();
+        match self {
+            IntegratorKind::Whitted => Box::new(Whitted { max_depth: cam.max_depth }),
+            IntegratorKind::Path => Box::new(PathTracer { max_depth: cam.max_depth }),
+            IntegratorKind::Ao => Box::new(AmbientOcclusion { distance: 0.25 * distance }),
+            IntegratorKind::Normals => Box::new(Normals),
+            IntegratorKind::Depth => Box::new(Depth { far: 2.0 * distance }),
+            IntegratorKind::Uv => Box::new(Uv),
+            IntegratorKind::Material => Box::new(MaterialId::new(materials)),
+        }
+    }
+}
+
+// Unidirectional path tracer. Follows one path for at most `max_depth` hits,
+// carrying the fraction of light that survives every bounce so far.
+//
+// Surfaces that sample the lights directly can also hit the same light with
+// their bounce. Both are weighted by the power heuristic against the density
+// the other would pick that direction with, so together they count the light
+// once.
+pub struct PathTracer {
+    pub max_depth: usize,
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> Color {
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut r = *r;
+        // Density the last surface picked `r` with, if it also sampled the lights
+        let mut bsdf_pdf: Option<f64> = None;
+
+        for bounce in 0..self.max_depth {
+            let Some(rec) = world.hit(&r, Interval::new(0.001, f64::INFINITY)) else {
+                radiance += throughput * sky(&r);
+                break;
+            };
+
+            // Add object's own emission (brightness)
+            let mut emission = r.carried(rec.mat.emission(&rec));
+            if let Some(bsdf_pdf) = bsdf_pdf
+                && rec.mat.is_emissive() {
+                emission = power_heuristic(bsdf_pdf, light_pdf(&r, &rec, lights)) * emission;
+            }
+            radiance += throughput * emission;
+
+            // Continue with material scattering
+            let Some((attenuation, scattered)) = rec.mat.scatter(&r, &rec) else {
+                // Absorbed, e.g. by a light
+                break;
+            };
+            let scattered = continue_path(&r, scattered);
+
+            // Mirrors and glass can't use light sampling: whatever light they
+            // pass on has to be found by the scattered ray
+            bsdf_pdf = None;
+            if rec.mat.lobes().direct_lighting {
+                let wo = -r.direction.unit_vector();
+                radiance += throughput * direct_light(&rec, &r, world, lights, true);
+                // Light sampling can only have found the next hit's emission if
+                // the bounce could have been picked by evaluating the BSDF
+                let pdf = rec.mat.pdf(&rec, wo, scattered.direction.unit_vector());
+                bsdf_pdf = (pdf > 0.0).then_some(pdf);
+            }
+            throughput = throughput * r.carried(attenuation);
+
+            // Russian roulette: past the first few bounces, dim paths are
+            // ended at random and the survivors brightened to make up for it
+            if bounce + 1 >= ROULETTE_DEPTH {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if random_f64() >= survival {
+                    break;
+                }
+                throughput = (1.0 / survival) * throughput;
+            }
+            r = scattered;
+        }
+        radiance
+    }
+}
+
+// Classic recursive ray tracing: surfaces that can be lit directly show only
+// their direct light, while mirrors and glass pass on whatever their
+// reflected or refracted ray sees. No light bounces between diffuse surfaces,
+// so it's fast and noise-free apart from soft shadows.
+pub struct Whitted {
+    pub max_depth: usize,
+}
+
+impl Integrator for Whitted {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, lights: &[Light]) -> Color {
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut r = *r;
+
+        for _ in 0..self.max_depth {
+            let Some(rec) = world.hit(&r, Interval::new(0.001, f64::INFINITY)) else {
+                radiance += throughput * sky(&r);
+                break;
+            };
+            radiance += throughput * r.carried(rec.mat.emission(&rec));
+            if rec.mat.lobes().direct_lighting {
+                radiance += throughput * direct_light(&rec, &r, world, lights, false);
+                break;
+            }
+            let Some((attenuation, scattered)) = rec.mat.scatter(&r, &rec) else { break };
+            throughput = throughput * r.carried(attenuation);
+            r = continue_path(&r, scattered);
+        }
+        radiance
+    }
+}
+
+// How open the sky is above the first surface hit: grey from black (fully
+// hemmed in) to white, looking for occluders up to `distance` away
+pub struct AmbientOcclusion {
+    pub distance: f64,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, _lights: &[Light]) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else { return white };
+        // Cosine-weighted, so the average is the ambient light received
+        let mut direction = rec.normal + Vec3::random_unit_vector();
+        if direction.near_zero() {
+            direction = rec.normal;
+        }
+        let probe = Ray::new(rec.p + 0.001 * rec.normal, direction.unit_vector());
+        match world.hit(&probe, Interval::new(0.001, self.distance)) {
+            Some(_) => Color::new(0.0, 0.0, 0.0),
+            None => white,
+        }
+    }
+}
+
+// Surface normal facing the camera, mapped from [-1, 1] to [0, 1] per axis
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, _lights: &[Light]) -> Color {
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else { return Color::new(0.0, 0.0, 0.0) };
+        let n = rec.normal;
+        0.5 * Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0)
+    }
+}
+
+// Distance to the first hit, white up close fading to black at `far`
+pub struct Depth {
+    pub far: f64,
+}
+
+impl Integrator for Depth {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, _lights: &[Light]) -> Color {
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else { return Color::new(0.0, 0.0, 0.0) };
+        let v = (1.0 - rec.t * r.direction.length() / self.far).max(0.0);
+        Color::new(v, v, v)
+    }
+}
+
+// Texture coordinates, u in red and v in green
+pub struct Uv;
+
+impl Integrator for Uv {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, _lights: &[Light]) -> Color {
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else { return Color::new(0.0, 0.0, 0.0) };
+        Color::new(rec.u, rec.v, 0.0)
+    }
+}
+
+// One flat colour per material, so objects sharing a material match. The
+// colour comes from the material's name in the library, so it's the same on
+// every run; materials the library doesn't hold (those from an OBJ's MTL
+// file, say) are all grey.
+pub struct MaterialId {
+    // Keyed by address only to find a hit material's entry
+    colors: HashMap<usize, Color>,
+}
+
+impl MaterialId {
+    pub fn new(materials: &MaterialLibrary) -> MaterialId {
+        let colors = materials.iter()
+            .map(|(name, mat)| (Arc::as_ptr(mat) as *const () as usize, name_color(name)))
+            .collect();
+        MaterialId { colors }
+    }
+}
+
+impl Integrator for MaterialId {
+    fn radiance(&self, r: &Ray, world: &dyn Hittable, _lights: &[Light]) -> Color {
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else { return Color::new(0.0, 0.0, 0.0) };
+        let id = rec.mat as *const dyn Material as *const () as usize;
+        self.colors.get(&id).copied().unwrap_or(Color::new(0.5, 0.5, 0.5))
+    }
+}
+
+// FNV-1a, which unlike the std hashers is fixed across runs and releases
+fn name_color(name: &str) -> Color {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    let hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(56), channel(48), channel(40))
+}
+
+fn sky(r: &Ray) -> Color {
+    let unit_direction = r.direction.unit_vector();
+    let a = 0.5 * (unit_direction.y + 1.0);
+    r.carried((1.0 - a)*Color::new(1.0, 1.0, 1.0) + a*Color::new(0.5, 0.7, 1.0))
+}
+
+// The whole path belongs to the camera sample's wavelength
+fn continue_path(r: &Ray, mut scattered: Ray) -> Ray {
+    scattered.wavelength = r.wavelength;
+    scattered.monochromatic |= r.monochromatic;
+    scattered
+}
+
+// Light arriving straight from the light sources and reflected back along
+// `r`, one sample per light. With `mis`, area light samples are weighted
+// against the BSDF also finding the light.
+fn direct_light(rec: &HitRecord, r: &Ray, world: &dyn Hittable, lights: &[Light], mis: bool) -> Color {
+    let mut direct_light = Color::new(0.0, 0.0, 0.0);
+    let wo = -r.direction.unit_vector();
+    // Shadow rays start just off the surface, on the light's side, to
+    // avoid self-intersection. Only transmissive surfaces are lit from
+    // behind.
+    let transmissive = rec.mat.lobes().transmissive;
+    let offset = |to_light: Vec3| {
+        let side = Vec3::dot(rec.normal, to_light);
+        (side > 0.0 || (transmissive && side < 0.0)).then(|| rec.p + (0.001 * side.signum()) * rec.normal)
+    };
+
+    for light in lights {
+        match light {
+            Light::Point { position, color, intensity } => {
+                let Some(offset_point) = offset(*position - rec.p) else { continue };
+                let light_dir = (*position - offset_point).unit_vector();
+                let distance_to_light = (*position - offset_point).length();
+
+                // Cast shadow ray
+                let shadow_ray = Ray::new(offset_point, light_dir);
+                if world.hit(&shadow_ray, Interval::new(0.001, distance_to_light)).is_none() {
+                    // Scaled by pi so a white Lambertian surface facing
+                    // the light shows exactly `intensity`
+                    let reflected = r.carried(rec.mat.eval(rec, wo, light_dir));
+                    direct_light += (PI * *intensity) * (r.carried(*color) * reflected);
+                }
+            }
+            Light::Area(shape) => {
+                let Some(sample) = shape.sample(rec.p) else { continue };
+                let Some(offset_point) = offset(sample.p - rec.p) else { continue };
+                if !sample.pdf.is_finite() {
+                    continue;
+                }
+                let to_light = sample.p - offset_point;
+                let distance_to_light = to_light.length();
+                let light_dir = to_light / distance_to_light;
+
+                // The light is visible if the shadow ray's first hit is the
+                // sampled point; that hit also gives the emission there
+                let shadow_ray = Ray::new(offset_point, light_dir);
+                let tolerance = 1e-4 * (1.0 + distance_to_light);
+                let reach = Interval::new(0.001, distance_to_light + tolerance);
+                if let Some(light_rec) = world.hit(&shadow_ray, reach)
+                    && light_rec.t > distance_to_light - tolerance {
+                    let reflected = r.carried(rec.mat.eval(rec, wo, light_dir));
+                    let weight = if mis { power_heuristic(sample.pdf, rec.mat.pdf(rec, wo, light_dir)) } else { 1.0 };
+                    direct_light += (weight / sample.pdf) * (reflected * r.carried(light_rec.mat.emission(&light_rec)));
+                }
+            }
+        }
+    }
+    direct_light
+}
+
+// Density with which `direct_light` would have picked the point `r` hits at
+// `rec`, from whichever area light it lies on
+fn light_pdf(r: &Ray, rec: &HitRecord, lights: &[Light]) -> f64 {
+    let reach = Interval::new(0.001, rec.t * (1.0 + 1e-6) + 1e-6);
+    lights.iter()
+        .map(|light| match light {
+            Light::Area(shape) => shape.sample_pdf(r, reach),
+            Light::Point { .. } => 0.0,
+        })
+        .sum()
+}
+
+// Weight for a sample drawn with density `pdf` when another strategy could
+// have drawn it with density `other` (Veach's power heuristic, beta = 2)
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a.is_infinite() { 1.0 } else { a / (a + b) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use crate::hittable_list::HittableList;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::disk::Disk;
+    use crate::material::{Lambertian, Dialectric, DiffuseLight};
+    use crate::vec3::Point3;
+    use crate::spectrum;
+
+    #[test]
+    fn test_lighting_follows_lobes() {
+        // A white floor under a point light; with one bounce only the
+        // direct light is left
+        let white = Color::new(1.0, 1.0, 1.0);
+        let mut world = HittableList::new();
+        world.add(Plane::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0), Arc::new(Lambertian::new(white, 0.0))));
+        let lights = [Light::new(Point3::new(0.0, 5.0, 0.0), white, 0.8)];
+        let path = PathTracer { max_depth: 1 };
+        let down = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let c = path.radiance(&down, &world, &lights);
+        assert!((c.r - 0.8).abs() < 1e-9, "white floor got {}", c.r);
+
+        // Glass is never lit directly, whatever colour it passes on
+        world.add(Sphere::new(Point3::new(0.0, 3.0, 0.0), 0.5, Arc::new(Dialectric::new(1.5, 0.0))));
+        let at_glass = Ray::new(Point3::new(0.0, 3.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        let c = path.radiance(&at_glass, &world, &lights);
+        assert_eq!((c.r, c.g, c.b), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_spectral_matches_rgb() {
+        // A coloured floor under a white light comes out about the same
+        // colour either way
+        let mut world = HittableList::new();
+        world.add(Plane::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0), Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1), 0.0))));
+        let lights = [Light::new(Point3::new(0.0, 5.0, 0.0), Color::new(1.0, 1.0, 1.0), 1.0)];
+        let path = PathTracer { max_depth: 1 };
+        let mut down = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rgb = path.radiance(&down, &world, &lights);
+
+        down.monochromatic = true;
+        let n = 1000;
+        let mut spectral = Color::new(0.0, 0.0, 0.0);
+        for i in 0..n {
+            down.wavelength = spectrum::sample_wavelength((i as f64 + 0.5) / n as f64);
+            let radiance = path.radiance(&down, &world, &lights);
+            spectral += (1.0 / n as f64) * (spectrum::film_weight(down.wavelength) * radiance);
+        }
+        for (a, b) in [(spectral.r, rgb.r), (spectral.g, rgb.g), (spectral.b, rgb.b)] {
+            assert!((a - b).abs() < 0.05, "spectral {a}, rgb {b}");
+        }
+    }
+
+    #[test]
+    fn test_mis_converges() {
+        // A grey floor lit by one light, closed in by a black shell so the sky
+        // stays out. Light sampling and the bounce both find the light, and
+        // together they should give the analytic answer.
+        let check = |light: Arc<dyn Hittable>, expected: f64| {
+            let mut world = HittableList::new();
+            world.add(Plane::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0), Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0))));
+            world.add(Sphere::new(Point3::zero(), 100.0, Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0), 0.0))));
+            world.add(Arc::clone(&light));
+            let lights = [Light::Area(light)];
+            let path = PathTracer { max_depth: 2 };
+            let down = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+            let n = 100_000;
+            let mut sum = 0.0;
+            for _ in 0..n {
+                sum += path.radiance(&down, &world, &lights).r;
+            }
+            let got = sum / n as f64;
+            assert!((got - expected).abs() < 0.02 * expected, "got {got}, expected {expected}");
+        };
+        let emit = || Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0), 1.0));
+
+        // Sphere of radius 1 at height 3: albedo * L * r^2 / d^2
+        check(Arc::new(Sphere::new(Point3::new(0.0, 3.0, 0.0), 1.0, emit())), 0.5 * 4.0 / 9.0);
+        // Disk of radius 1.5 at height 2: albedo * L * r^2 / (r^2 + h^2)
+        check(Arc::new(Disk::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 1.5, emit())), 0.5 * 4.0 * 2.25 / 6.25);
+    }
+
+    #[test]
+    fn test_russian_roulette_is_unbiased() {
+        // Inside a closed sphere that glows and reflects 80%, light bounces
+        // forever: the radiance is emission / (1 - albedo). Roulette has to
+        // end paths long before the cap without losing any of it.
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Point3::zero(), 1.0, Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8), 0.25))));
+        let path = PathTracer { max_depth: 10_000 };
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let n = 20_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            sum += path.radiance(&r, &world, &[]).r;
+        }
+        let got = sum / n as f64;
+        assert!((got - 1.0).abs() < 0.03, "got {got}");
+    }
+
+    #[test]
+    fn test_whitted_and_debug_views() {
+        // A glowing grey floor under a point light, and a second material
+        // on a sphere above it
+        let mut library = MaterialLibrary::new();
+        let grey = library.insert("grey", Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.2)));
+        let ball = library.insert("ball", Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0)));
+        let mut world = HittableList::new();
+        world.add(Plane::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0), Arc::clone(&grey)));
+        world.add(Sphere::new(Point3::new(3.0, 1.0, 0.0), 1.0, ball));
+        let lights = [Light::new(Point3::new(0.0, 5.0, 0.0), Color::new(1.0, 1.0, 1.0), 1.0)];
+        let down = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -2.0, 0.0));
+
+        // Emission plus direct light, and nothing bounced
+        let c = Whitted { max_depth: 10 }.radiance(&down, &world, &lights);
+        assert!((c.r - (0.1 + 0.5)).abs() < 1e-9, "{}", c.r);
+
+        let n = Normals.radiance(&down, &world, &lights);
+        assert_eq!((n.r, n.g, n.b), (0.5, 1.0, 0.5));
+        let d = Depth { far: 4.0 }.radiance(&down, &world, &lights);
+        assert!((d.r - 0.5).abs() < 1e-9);
+
+        // Same material, same colour; another material, another colour
+        let ids = MaterialId::new(&library);
+        let on_floor = ids.radiance(&Ray::new(Point3::new(1.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), &world, &lights);
+        let on_sphere = ids.radiance(&Ray::new(Point3::new(3.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), &world, &lights);
+        let m = ids.radiance(&down, &world, &lights);
+        assert_eq!((m.r, m.g, m.b), (on_floor.r, on_floor.g, on_floor.b));
+        assert_ne!((m.r, m.g, m.b), (on_sphere.r, on_sphere.g, on_sphere.b));
+        // The colour follows the name, not where the material happens to live
+        let expected = name_color("grey");
+        assert_eq!((m.r, m.g, m.b), (expected.r, expected.g, expected.b));
+        let g = MaterialId::new(&MaterialLibrary::new()).radiance(&down, &world, &lights);
+        assert_eq!((g.r, g.g, g.b), (0.5, 0.5, 0.5));
+
+        // Nothing above the open floor
+        let ao = AmbientOcclusion { distance: 1.0 }.radiance(&down, &world, &lights);
+        assert_eq!(ao.r, 1.0);
+    }
+}
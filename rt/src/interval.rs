@@ -1,12 +1,26 @@
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
 }
 
 impl Interval {
+    pub const EMPTY: Interval = Interval::new(f64::INFINITY, f64::NEG_INFINITY);
+
     pub const fn new(min: f64, max: f64) -> Interval {
         Interval { min, max }
     }
+    // Tightest interval containing both a and b
+    pub fn enclosing(a: Interval, b: Interval) -> Interval {
+        Interval::new(a.min.min(b.min), a.max.max(b.max))
+    }
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
@@ -19,4 +33,4 @@ impl Interval {
             x
         }
     }
-}
\ No newline at end of file
+}
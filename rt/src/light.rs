@@ -1,15 +1,18 @@
+use std::sync::Arc;
 use crate::vec3::Point3;
 use crate::color::Color;
+use crate::hittable::Hittable;
 
-pub struct Light {
-    pub position: Point3,
-    pub color: Color,
-    pub intensity: f64,
+pub enum Light {
+    // Infinitely small, so its shadows are hard
+    Point { position: Point3, color: Color, intensity: f64 },
+    // Emissive geometry that is also part of the world; sampled over its
+    // surface, which gives soft shadows
+    Area(Arc<dyn Hittable>),
 }
 
 impl Light {
     pub fn new(position: Point3, color: Color, intensity: f64) -> Light {
-        Light { position, color, intensity }
+        Light::Point { position, color, intensity }
     }
 }
-
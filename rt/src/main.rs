@@ -1,6 +1,7 @@
 mod vec3;
 mod color;
 mod ray;
+mod spectrum;
 mod hittable;
 mod hittable_list;
 mod sphere;
@@ -9,254 +10,154 @@ mod cube;
 mod plane;
 mod interval;
 mod camera;
+mod integrator;
 mod util;
 mod material;
+mod microfacet;
 mod light;
+mod aabb;
+mod bvh;
+mod scene;
+mod scenes;
+mod framebuffer;
+mod triangle;
+mod obj;
+mod rect;
+mod disk;
+mod texture;
+mod image_texture;
+mod noise;
+mod transform;
+mod instance;
 
-use vec3::{Vec3, Point3};
-use sphere::Sphere;
-use cylinder::Cylinder;
-use cube::Cube;
-use plane::Plane;
-use hittable_list::HittableList;
-use camera::Camera; 
-use material::{Lambertian, Metal, Dialectric};
-use color::Color;
-use light::Light;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use clap::Parser;
+use clap::builder::PossibleValuesParser;
+use bvh::BvhNode;
+use scene::Scene;
+use scenes::BUILTIN_SCENES;
+use framebuffer::ImageFormat;
+use integrator::IntegratorKind;
 
-fn main() {
-    let mut world = HittableList::new();
-    
-    // ============================================================================
-    // SCENE 1: SPHERE SCENE
-    // ============================================================================
-    // Requirements: Scene with a sphere, clear shadows visible
-    // Uncomment this section to render Scene 1
-    
-    // Ground plane - Dark surface to show shadows clearly
-    // let ground = Lambertian::new(Color::new(0.2, 0.2, 0.2), 0.0);
-    // world.add(Plane::new(
-    //     Point3::new(0.0, 0.0, 0.0),
-    //     Vec3::new(0.0, 1.0, 0.0),
-    //     ground
-    // ));
-    
-    // // Bright sphere - High brightness to cast visible shadows
-    // let sphere_mat = Lambertian::new(Color::new(0.8, 0.3, 0.3), 0.6);
-    // world.add(Sphere::new(Point3::new(0.0, 1.5, 0.0), 1.0, sphere_mat));
-    
-    // // Light source positioned to create clear shadows
-    // let lights = vec![
-    //     Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.0),
-    // ];
-    
-    // let mut cam = Camera::new();
-    // cam.aspect_ratio = 16.0/9.0;
-    // cam.image_width = 600;
-    // cam.samples_per_pixel = 500;
-    // cam.max_depth = 50;
-    // cam.vfov = 20.0;
-    // cam.lookfrom = Point3::new(8.0, 3.0, 5.0);
-    // cam.lookat = Point3::new(0.0, 1.0, 0.0);
-    // cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    // cam.defocus_angle = 0.0;
-    // cam.focus_dist = 10.0;
-    
-    // cam.render(&world, &lights);
-    
-    
-    // ============================================================================
-    // SCENE 2: PLANE AND CUBE (LOWER BRIGHTNESS)
-    // ============================================================================
-    // Requirements: Flat plane and cube with lower brightness than Scene 1 sphere
-    // Uncomment this section to render Scene 2
-    /*
-    // Ground plane - Flat surface
-    let ground = Lambertian::new(Color::new(0.25, 0.25, 0.25), 0.0);
-    world.add(Plane::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        ground
-    ));
-    
-    // Cube with lower brightness than Scene 1 sphere (0.3 < 0.6)
-    let cube_mat = Lambertian::new(Color::new(0.3, 0.5, 0.8), 0.3);
-    world.add(Cube::from_center_size(
-        Point3::new(0.0, 1.0, 0.0),
-        1.5,
-        cube_mat
-    ));
-    
-    // Light source
-    let lights = vec![
-        Light::new(Point3::new(4.0, 5.0, 2.0), Color::new(1.0, 1.0, 1.0), 1.0),
-    ];
-    
-    let mut cam = Camera::new();
-    cam.aspect_ratio = 16.0/9.0;
-    cam.image_width = 600;
-    cam.samples_per_pixel = 500;
-    cam.max_depth = 50;
-    cam.vfov = 20.0;
-    cam.lookfrom = Point3::new(7.0, 3.0, 4.0);
-    cam.lookat = Point3::new(0.0, 1.0, 0.0);
-    cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    cam.defocus_angle = 0.0;
-    cam.focus_dist = 10.0;
+const DEFAULT_SCENE: &str = "cylinders";
 
-    cam.render(&world, &lights);
-    
-    */
-    
-    // ============================================================================
-    // SCENE 3: ALL OBJECTS (ONE OF EACH)
-    // ============================================================================
-    // Requirements: One cube, one sphere, one cylinder, one flat plane
-    // Clear shadows visible from all objects
-    // Uncomment this section to render Scene 3
-    /*
-    // Flat plane (ground)
-    let ground = Lambertian::new(Color::new(0.3, 0.3, 0.3), 0.0);
-    world.add(Plane::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        ground
-    ));
-    
-    // One sphere - positioned on the left
-    let sphere_mat = Lambertian::new(Color::new(0.8, 0.2, 0.2), 0.5);
-    world.add(Sphere::new(Point3::new(-2.5, 1.0, 0.0), 0.8, sphere_mat));
-    
-    // One cube - positioned in the center
-    let cube_mat = Lambertian::new(Color::new(0.2, 0.6, 0.8), 0.4);
-    world.add(Cube::from_center_size(
-        Point3::new(0.0, 1.0, 0.0),
-        1.2,
-        cube_mat
-    ));
-    
-    // One cylinder - positioned on the right
-    let cylinder_mat = Lambertian::new(Color::new(0.6, 0.8, 0.2), 0.45);
-    world.add(Cylinder::new(Point3::new(2.5, 1.0, 0.0), 0.6, 2.0, cylinder_mat));
-    
-    // Light sources positioned to create clear shadows
-    let lights = vec![
-        Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.2),
-        Light::new(Point3::new(-4.0, 4.0, 2.0), Color::new(1.0, 0.95, 0.9), 0.8),
-    ];
-    
-    let mut cam = Camera::new();
-    cam.aspect_ratio = 16.0/9.0;
-    cam.image_width = 600;
-    cam.samples_per_pixel = 500;
-    cam.max_depth = 50;
-    cam.vfov = 20.0;
-    cam.lookfrom = Point3::new(8.0, 3.0, 5.0);
-    cam.lookat = Point3::new(0.0, 1.0, 0.0);
-    cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    cam.defocus_angle = 0.0;
-    cam.focus_dist = 10.0;
+/// Render a scene file or one of the built-in scenes to a PPM, PNG or PFM image
+#[derive(Parser)]
+struct Cli {
+    /// TOML scene file to render
+    #[arg(conflicts_with = "scene")]
+    scene_file: Option<PathBuf>,
 
-    cam.render(&world, &lights);
+    /// Render a built-in scene instead of a scene file
+    #[arg(short, long, value_parser = PossibleValuesParser::new(BUILTIN_SCENES.map(|(name, _)| name)))]
+    scene: Option<String>,
 
-    */
-    
-    // ============================================================================
-    // SCENE 4: ALL OBJECTS FROM DIFFERENT PERSPECTIVE
-    // ============================================================================
-    // Requirements: Same objects as Scene 3, but camera in different position
-    // Uncomment this section to render Scene 4
-    /*
-    // Flat plane (ground) - same as Scene 3
-    let ground = Lambertian::new(Color::new(0.3, 0.3, 0.3), 0.0);
-    world.add(Plane::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        ground
-    ));
-    
-    // One sphere - Mirror (reflective)
-    let sphere_mat = Metal::new(Color::new(0.9, 0.9, 0.9), 0.0, 0.0);  // Perfect mirror
-    world.add(Sphere::new(Point3::new(-2.5, 1.0, 0.0), 0.8, sphere_mat));
-    
-    // One cube - same position as Scene 3
-    let cube_mat = Lambertian::new(Color::new(0.2, 0.6, 0.8), 0.4);
-    world.add(Cube::from_center_size(
-        Point3::new(0.0, 1.0, 0.0),
-        1.2,
-        cube_mat
-    ));
-    
-    // One cylinder - same position as Scene 3
-    let cylinder_mat = Lambertian::new(Color::new(0.6, 0.8, 0.2), 0.45);
-    world.add(Cylinder::new(Point3::new(2.5, 1.0, 0.0), 0.6, 2.0, cylinder_mat));
-    
-    // Same light sources as Scene 3
-    let lights = vec![
-        Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.2),
-        //Light::new(Point3::new(-4.0, 4.0, 2.0), Color::new(1.0, 0.95, 0.9), 0.8),
-    ];
-    
-    let mut cam = Camera::new();
-    cam.aspect_ratio = 16.0/9.0;
-    cam.image_width = 600;
-    cam.samples_per_pixel = 500;
-    cam.max_depth = 50;
-    cam.vfov = 35.0;  // Increased field of view to capture all objects
-    // Different camera position - viewing from front, moved back and up to see all objects
-    cam.lookfrom = Point3::new(0.0, 3.5, 10.0);  // Moved back and slightly lower
-    cam.lookat = Point3::new(0.0, 1.0, 0.0);  // Looking at center of scene
-    cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    cam.defocus_angle = 0.0;
-    cam.focus_dist = 12.0;  // Increased focus distance
+    /// Write the image here instead of to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 
-    cam.render(&world, &lights);
-    */
-    
-    // ============================================================================
-    // SCENE 5: TWO CYLINDERS AT DIFFERENT ANGLES
-    // ============================================================================
-    // Two cylinders with different colors, positioned at different angles
-    
-    // Ground plane
-    let ground = Lambertian::new(Color::new(0.3, 0.3, 0.3), 0.0);
-    world.add(Plane::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        ground
-    ));
-    
-    // First cylinder - Red/Orange, positioned on the left, taller
-    let cylinder1_mat = Lambertian::new(Color::new(0.8, 0.3, 0.2), 0.3);
-    world.add(Cylinder::new(Point3::new(-2.0, 1.5, 0.0), 0.5, 3.0, cylinder1_mat));
-    
-    // Second cylinder - Blue/Purple, positioned on the right, shorter and wider
-    let cylinder2_mat = Lambertian::new(Color::new(0.2, 0.4, 0.9), 0.35);
-    world.add(Cylinder::new(Point3::new(2.0, 1.0, 0.0), 0.7, 2.0, cylinder2_mat));
-    
-    // Light source positioned to show shadows clearly
-    let lights = vec![
-        Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.2),
-    ];
-    
-    let mut cam = Camera::new();
-    cam.aspect_ratio = 16.0/9.0;
-    cam.image_width = 600;
-    cam.samples_per_pixel = 500;
-    cam.max_depth = 50;
-    cam.vfov = 30.0;
-    cam.lookfrom = Point3::new(0.0, 3.0, 8.0);
-    cam.lookat = Point3::new(0.0, 1.0, 0.0);
-    cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    cam.defocus_angle = 0.0;
-    cam.focus_dist = 10.0;
+    /// Image format [default: from the output extension, otherwise p3]
+    #[arg(short, long)]
+    format: Option<ImageFormat>,
 
-    cam.render(&world, &lights);
-    
-    
-    // ============================================================================
-    // NOTE: All scenes are commented out by default
-    // Uncomment ONE scene section above to render it
-    // Make sure to comment out the default scene if you uncomment a test scene
-    // ============================================================================
+    /// Override the image width in pixels
+    #[arg(long)]
+    image_width: Option<NonZeroUsize>,
+
+    /// Override the number of samples per pixel
+    #[arg(long)]
+    samples_per_pixel: Option<NonZeroUsize>,
+
+    /// Override the safety cap on bounces per path
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Override the aspect ratio, as a number (1.78) or width:height (16:9)
+    #[arg(long, value_parser = parse_aspect_ratio)]
+    aspect_ratio: Option<f64>,
+
+    /// Trace one wavelength per sample instead of RGB, for accurate color
+    /// with saturated lights and colored inter-reflection
+    #[arg(long)]
+    spectral: bool,
+
+    /// Shade with another integrator, e.g. a debug view
+    #[arg(long)]
+    integrator: Option<IntegratorKind>,
+
+    /// Print a summary of the scene and exit without rendering
+    #[arg(long)]
+    summary: bool,
+}
+
+fn parse_aspect_ratio(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = w.trim().parse().map_err(|_| format!("invalid width `{w}`"))?;
+            let h: f64 = h.trim().parse().map_err(|_| format!("invalid height `{h}`"))?;
+            w / h
+        }
+        None => s.parse().map_err(|_| format!("invalid aspect ratio `{s}`"))?,
+    };
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("aspect ratio must be positive, got `{s}`"))
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let mut scene = match &cli.scene_file {
+        Some(path) => match Scene::load(path) {
+            Ok(scene) => scene,
+            Err(err) => {
+                eprintln!("{}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => scenes::builtin(cli.scene.as_deref().unwrap_or(DEFAULT_SCENE))
+            .expect("clap only accepts built-in scene names"),
+    };
+
+    let cam = &mut scene.camera;
+    if let Some(v) = cli.image_width { cam.image_width = v.get(); }
+    if let Some(v) = cli.samples_per_pixel { cam.samples_per_pixel = v.get(); }
+    if let Some(v) = cli.max_depth { cam.max_depth = v; }
+    if let Some(v) = cli.aspect_ratio { cam.aspect_ratio = v; }
+    if cli.spectral { cam.spectral = true; }
+    if let Some(v) = cli.integrator { cam.integrator = v; }
+
+    if cli.summary {
+        print!("{}", scene.summary());
+        return ExitCode::SUCCESS;
+    }
+
+    let format = cli.format
+        .or_else(|| cli.output.as_ref()?.extension()?.to_str().and_then(ImageFormat::from_extension))
+        .unwrap_or(ImageFormat::P3);
+
+    let mut out: Box<dyn Write> = match &cli.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("{}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+
+    let mut cam = scene.camera;
+    let image = cam.render(&BvhNode::new(scene.world), &scene.lights, &scene.materials);
+    if let Err(err) = image.write(&mut out, format) {
+        eprintln!("error writing image: {err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
@@ -1,49 +1,140 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::Arc;
 use crate::hittable::HitRecord;
 use crate::color::Color;
+use crate::texture::{Texture, SolidColor};
 use crate::ray::Ray;
 use crate::vec3::{Vec3};
 use crate::util::random_f64;
+use crate::spectrum;
 
-pub trait Material: Sync {
+// What kinds of scattering a material does, so the integrator can branch on
+// them instead of guessing from colours
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lobes {
+    // Perfectly sharp reflection or refraction (mirrors, smooth glass): only
+    // `scatter` can find these directions, `eval` never sees them
+    pub delta: bool,
+    // Lets light through to the other side of the surface
+    pub transmissive: bool,
+    // Worth sampling the lights from; false for delta-only materials and
+    // pure emitters
+    pub direct_lighting: bool,
+}
+
+pub trait Material: Send + Sync {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
-    fn emission(&self) -> Color;
-} 
+    fn lobes(&self) -> Lobes;
+    // BSDF times the cosine at `wi`: how much of the light arriving from `wi`
+    // leaves towards `wo`. Both are unit vectors pointing away from the hit
+    // point. Mirror-like (delta) reflection can't be evaluated for a given
+    // pair of directions, so the default is black.
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+    // Density with which `scatter` picks `wi` when leaving towards `wo`, per
+    // unit solid angle; zero for directions only a delta lobe produces
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> f64 {
+        0.0
+    }
+    // Light given off at the hit point
+    fn emission(&self, rec: &HitRecord) -> Color;
+    // Whether the material gives off any light at all; objects using it are
+    // sampled as area lights
+    fn is_emissive(&self) -> bool;
+}
+
+// Materials by name. Objects hold an Arc to theirs, so one material can drive
+// any number of objects; replacing a name only affects objects built after.
+#[derive(Default)]
+pub struct MaterialLibrary {
+    materials: HashMap<String, Arc<dyn Material>>,
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary::default()
+    }
+
+    // Returns the material so it can be handed straight to an object
+    pub fn insert(&mut self, name: impl Into<String>, mat: Arc<dyn Material>) -> Arc<dyn Material> {
+        self.materials.insert(name.into(), Arc::clone(&mat));
+        mat
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Material>> {
+        self.materials.get(name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.materials.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<dyn Material>)> {
+        self.materials.iter().map(|(name, mat)| (name.as_str(), mat))
+    }
+}
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     brightness: f64
 }
 
 impl Lambertian {
     pub fn new(albedo: Color, brightness: f64) -> Lambertian {
+        Lambertian::from_texture(Arc::new(SolidColor::new(albedo)), brightness)
+    }
+    pub fn from_texture(albedo: Arc<dyn Texture>, brightness: f64) -> Lambertian {
         Lambertian { albedo, brightness }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
         let scattered = Ray::new(rec.p, scatter_direction);
-        let attentuation = self.albedo;
+        let attentuation = self.albedo.value(rec.u, rec.v, rec.p);
         Some((attentuation, scattered))
     }
+
+    fn lobes(&self) -> Lobes {
+        Lobes { direct_lighting: true, ..Default::default() }
+    }
+
+    fn eval(&self, rec: &HitRecord, _wo: Vec3, wi: Vec3) -> Color {
+        let cosine = Vec3::dot(rec.normal, wi).max(0.0);
+        (cosine / PI) * self.albedo.value(rec.u, rec.v, rec.p)
+    }
+
+    // normal + random unit vector is cosine distributed
+    fn pdf(&self, rec: &HitRecord, _wo: Vec3, wi: Vec3) -> f64 {
+        Vec3::dot(rec.normal, wi).max(0.0) / PI
+    }
     
-    fn emission(&self) -> Color {
-        self.brightness * self.albedo
+    fn emission(&self, rec: &HitRecord) -> Color {
+        self.brightness * self.albedo.value(rec.u, rec.v, rec.p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.brightness > 0.0
     }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
     brightness: f64
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64, brightness: f64) -> Metal {
+        Metal::from_texture(Arc::new(SolidColor::new(albedo)), fuzz, brightness)
+    }
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64, brightness: f64) -> Metal {
         Metal { albedo, fuzz: if fuzz < 1.0 { fuzz} else {1.0}, brightness }
     }
 }
@@ -52,7 +143,7 @@ impl Material for Metal {
         let mut reflected = Vec3::reflect(r_in.direction, rec.normal);
         reflected = reflected.unit_vector() + (self.fuzz * Vec3::random_unit_vector());
         let scattered = Ray::new(rec.p, reflected);
-        let attentuation = self.albedo;
+        let attentuation = self.albedo.value(rec.u, rec.v, rec.p);
         if Vec3::dot(scattered.direction, rec.normal) > 0.0 {
             Some((attentuation, scattered))
         }else {
@@ -60,20 +151,94 @@ impl Material for Metal {
         }
         
     }
+
+    // Without fuzz it's a perfect mirror
+    fn lobes(&self) -> Lobes {
+        let delta = self.fuzz <= 0.0;
+        Lobes { delta, direct_lighting: !delta, ..Default::default() }
+    }
+
+    // Every direction scatter keeps is weighted by the albedo alone, so the
+    // BSDF times cosine is just the albedo times the pdf
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        self.pdf(rec, wo, wi) * self.albedo.value(rec.u, rec.v, rec.p)
+    }
+
+    // Scatter aims at a uniform point on a sphere of radius fuzz around the
+    // mirror direction. Each place the ray along `wi` crosses that sphere
+    // adds its area density, converted to solid angle.
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64 {
+        if self.fuzz <= 0.0 || Vec3::dot(rec.normal, wi) <= 0.0 {
+            return 0.0;
+        }
+        let mirror = Vec3::reflect(-wo, rec.normal).unit_vector();
+        // |t wi - mirror| = fuzz
+        let b = Vec3::dot(wi, mirror);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let sqrtd = discriminant.sqrt();
+        [b - sqrtd, b + sqrtd].iter()
+            .filter(|&&t| t > 0.0)
+            .map(|t| t * t / (4.0 * PI * self.fuzz * sqrtd))
+            .sum()
+    }
     
-    fn emission(&self) -> Color {
-        self.brightness * self.albedo
+    fn emission(&self, rec: &HitRecord) -> Color {
+        self.brightness * self.albedo.value(rec.u, rec.v, rec.p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.brightness > 0.0
+    }
+}
+
+// How a refractive index varies with wavelength. Coefficients use
+// wavelengths in micrometres, as in optical glass catalogues.
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / l^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum of b_i l^2 / (l^2 - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn index(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
     }
 }
 
 pub struct Dialectric {
     refraction_index: f64,
+    dispersion: Option<Dispersion>,  // overrides refraction_index per wavelength
+    tint: Color,        // applied each time light refracts through the surface
+    absorption: Color,  // per unit distance travelled inside
     brightness: f64
 }
 
 impl Dialectric {
     pub fn new(refraction_index: f64, brightness: f64) -> Dialectric {
-        Dialectric { refraction_index, brightness }
+        Dialectric::colored(refraction_index, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0), brightness)
+    }
+    // Coloured glass, liquids and gems. Light inside is absorbed following
+    // Beer-Lambert, exp(-absorption * distance), so thick parts get darker
+    // and more saturated than thin ones.
+    pub fn colored(refraction_index: f64, tint: Color, absorption: Color, brightness: f64) -> Dialectric {
+        Dialectric { refraction_index, dispersion: None, tint, absorption, brightness }
+    }
+    // Splits white light into a spectrum, like a prism or a diamond
+    pub fn dispersive(dispersion: Dispersion, tint: Color, absorption: Color, brightness: f64) -> Dialectric {
+        // Index at the helium d line, the usual reference
+        let refraction_index = dispersion.index(587.6);
+        Dialectric { refraction_index, dispersion: Some(dispersion), tint, absorption, brightness }
     }
     pub fn reflectance(&self, cosine: f64, refraction_index: f64) -> f64 {
         let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
@@ -84,27 +249,191 @@ impl Dialectric {
 
 impl Material for Dialectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let attentuation = Color::new(1.0, 1.0, 1.0);
+        let mut attentuation = if rec.front_face {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            // Hit from inside: the ray came straight from where it entered
+            // or last reflected, all of it through the medium
+            let distance = rec.t * r_in.direction.length();
+            let a = self.absorption;
+            Color::new((-a.r * distance).exp(), (-a.g * distance).exp(), (-a.b * distance).exp())
+        };
+        let mut refraction_index = self.refraction_index;
+        if let Some(dispersion) = &self.dispersion {
+            refraction_index = dispersion.index(r_in.wavelength);
+            // From here on the path only carries this wavelength. Nothing it
+            // gathered before depended on it, so this is where it's turned
+            // into colour, once.
+            if !r_in.monochromatic {
+                attentuation = attentuation * spectrum::film_weight(r_in.wavelength);
+            }
+        }
         let ri = if rec.front_face {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
         let unit_direction = r_in.direction.unit_vector();
         let cos_theta = Vec3::dot(-unit_direction, rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction;
-        if cannot_refract || self.reflectance(cos_theta, ri) > random_f64() {
-            direction = Vec3::reflect(unit_direction, rec.normal);
+        let direction = if cannot_refract || self.reflectance(cos_theta, ri) > random_f64() {
+            Vec3::reflect(unit_direction, rec.normal)
         } else {
-            direction = Vec3::refract(unit_direction, rec.normal, ri);
-        }
-        let scattered = Ray::new(rec.p, direction);
+            attentuation = attentuation * self.tint;
+            Vec3::refract(unit_direction, rec.normal, ri)
+        };
+        let mut scattered = Ray::new(rec.p, direction);
+        scattered.monochromatic = self.dispersion.is_some();
         Some((attentuation, scattered))
     }
+
+    fn lobes(&self) -> Lobes {
+        Lobes { delta: true, transmissive: true, direct_lighting: false }
+    }
     
-    fn emission(&self) -> Color {
+    fn emission(&self, _rec: &HitRecord) -> Color {
         self.brightness * Color::new(1.0, 1.0, 1.0)
     }
-}
\ No newline at end of file
+
+    fn is_emissive(&self) -> bool {
+        self.brightness > 0.0
+    }
+}
+
+// Pure emitter; absorbs everything that hits it
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(color: Color, intensity: f64) -> DiffuseLight {
+        DiffuseLight { emit: intensity * color }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn lobes(&self) -> Lobes {
+        Lobes::default()
+    }
+
+    fn emission(&self, _rec: &HitRecord) -> Color {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::Point3;
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::sphere::Sphere;
+    use crate::cube::Cube;
+
+    #[test]
+    fn test_metal_pdf_matches_scatter() {
+        // Bounce off a fuzzy metal floor and compare where scattered rays go,
+        // in bands of angle away from the mirror direction, with the pdf
+        let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.4, 0.0);
+        let rec = HitRecord::new(1.0, Point3::zero(), Vec3::new(0.0, 1.0, 0.0), &metal);
+        let r_in = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let wo = -r_in.direction.unit_vector();
+        let mirror = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+
+        // Rays spread up to asin(0.4) from the mirror direction
+        let (bands, spread) = (8, 0.4f64.asin());
+        let n = 200_000;
+        let mut counts = vec![0.0; bands];
+        for _ in 0..n {
+            if let Some((attenuation, scattered)) = metal.scatter(&r_in, &rec) {
+                let wi = scattered.direction.unit_vector();
+                let weight = metal.eval(&rec, wo, wi).r / metal.pdf(&rec, wo, wi);
+                assert!((weight - attenuation.r).abs() < 1e-9);
+                let angle = Vec3::dot(wi, mirror).min(1.0).acos();
+                counts[((angle / spread * bands as f64) as usize).min(bands - 1)] += 1.0 / n as f64;
+            }
+        }
+
+        let (u, v) = Vec3::orthonormal_basis(mirror);
+        let steps = 400;
+        for (band, count) in counts.iter().enumerate() {
+            let mut expected = 0.0;
+            for a in 0..steps {
+                let angle = spread * (band as f64 + (a as f64 + 0.5) / steps as f64) / bands as f64;
+                for b in 0..steps {
+                    let phi = 2.0 * PI * (b as f64 + 0.5) / steps as f64;
+                    let wi = angle.cos() * mirror + angle.sin() * (phi.cos() * u + phi.sin() * v);
+                    let solid_angle = angle.sin() * (spread / (bands * steps) as f64) * (2.0 * PI / steps as f64);
+                    expected += metal.pdf(&rec, wo, wi) * solid_angle;
+                }
+            }
+            assert!((count - expected).abs() < 0.01, "band {band}: scattered {count}, pdf {expected}");
+        }
+    }
+
+    #[test]
+    fn test_absorption_by_distance() {
+        // Straight through the middle of a glass ball and a glass cube, both
+        // 2 units of glass
+        let glass: Arc<dyn Material> = Arc::new(Dialectric::colored(1.5, Color::new(0.9, 1.0, 1.0), Color::new(0.0, 0.5, 1.0), 0.0));
+        let ball = Sphere::new(Point3::zero(), 1.0, Arc::clone(&glass));
+        let cube = Cube::from_center_size(Point3::zero(), 2.0, glass);
+        for object in [&ball as &dyn Hittable, &cube] {
+            let through = |r: &Ray| loop {
+                // Retry until the ray refracts rather than reflects
+                let rec = object.hit(r, Interval::new(0.001, f64::INFINITY)).unwrap();
+                let (attenuation, scattered) = rec.mat.scatter(r, &rec).unwrap();
+                if Vec3::dot(scattered.direction, r.direction) > 0.0 {
+                    break (attenuation, scattered);
+                }
+            };
+            let (entering, inside) = through(&Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0)));
+            assert_eq!((entering.r, entering.g, entering.b), (0.9, 1.0, 1.0));
+            let (leaving, _) = through(&inside);
+            let expected = [0.9, (-1.0f64).exp(), (-2.0f64).exp()];
+            for (got, want) in [leaving.r, leaving.g, leaving.b].into_iter().zip(expected) {
+                assert!((got - want).abs() < 1e-9, "{got} vs {want}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_dispersion() {
+        // N-BK7 from the Schott catalogue, at the F, d and C lines
+        let bk7 = Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        };
+        for (wavelength, n) in [(486.1, 1.5224), (587.6, 1.5168), (656.3, 1.5143)] {
+            assert!((bk7.index(wavelength) - n).abs() < 1e-4, "{wavelength}: {}", bk7.index(wavelength));
+        }
+        // A two-term Cauchy fit to the same glass
+        let cauchy = Dispersion::Cauchy { a: 1.5046, b: 0.00420 };
+        assert!((cauchy.index(587.6) - 1.5168).abs() < 1e-3);
+
+        // Averaged over the spectrum, dispersive clear glass passes white light
+        let glass = Dialectric::dispersive(bk7, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0), 0.0);
+        let rec = HitRecord::new(1.0, Point3::zero(), Vec3::new(0.0, 0.0, 1.0), &glass);
+        let n = 100_000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let mut r_in = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            r_in.wavelength = spectrum::sample_wavelength(random_f64());
+            let (attenuation, scattered) = glass.scatter(&r_in, &rec).unwrap();
+            assert!(scattered.monochromatic);
+            sum += (1.0 / n as f64) * attenuation;
+        }
+        for c in [sum.r, sum.g, sum.b] {
+            assert!((c - 1.0).abs() < 0.03, "{c}");
+        }
+    }
+}
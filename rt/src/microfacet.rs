@@ -0,0 +1,342 @@
+use std::f64::consts::PI;
+use std::sync::{Arc, OnceLock};
+use crate::hittable::HitRecord;
+use crate::color::Color;
+use crate::material::{Material, Lobes};
+use crate::texture::Texture;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::util::random_f64;
+
+// Roughness is squared into the GGX alpha; below this the distribution gets
+// too sharp for floating point, and the surface is as good as a mirror anyway
+const MIN_ALPHA: f64 = 1e-3;
+// Reflectance of a dielectric at normal incidence (index of refraction ~1.5)
+const DIELECTRIC_F0: f64 = 0.04;
+
+// Physically based surface: a GGX microfacet specular layer over a diffuse
+// base. `metallic` blends from plastic-like (coloured diffuse under a white
+// coat) to metal (coloured reflection, no diffuse); `roughness` runs from a
+// mirror at 0 to very matte at 1.
+pub struct Microfacet {
+    base_color: Arc<dyn Texture>,
+    roughness: f64,
+    metallic: f64,
+    brightness: f64,
+}
+
+impl Microfacet {
+    pub fn from_texture(base_color: Arc<dyn Texture>, roughness: f64, metallic: f64, brightness: f64) -> Microfacet {
+        Microfacet {
+            base_color,
+            roughness: roughness.clamp(0.0, 1.0),
+            metallic: metallic.clamp(0.0, 1.0),
+            brightness,
+        }
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).max(MIN_ALPHA)
+    }
+
+    // Colour of head-on reflection: 4% white for the dielectric part, the
+    // base colour for the metal part
+    fn f0(&self, base: Color) -> Color {
+        let dielectric = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        (1.0 - self.metallic) * dielectric + self.metallic * base
+    }
+
+    // Single scattering loses the light that bounces between microfacets,
+    // more so the rougher the surface; it's scaled back in (Turquin 2019).
+    // Returns that scale, and the total the specular layer then reflects.
+    fn specular_albedo(&self, f0: Color, cos_o: f64) -> (Color, Color) {
+        let (scale, bias) = directional_albedo(cos_o, self.roughness);
+        let e = (scale + bias).max(1e-3);
+        let compensation = Color::new(1.0, 1.0, 1.0) + ((1.0 - e) / e) * f0;
+        let single = scale * f0 + bias * Color::new(1.0, 1.0, 1.0);
+        (compensation, compensation * single)
+    }
+
+    // Chance of sampling the specular lobe rather than the diffuse one
+    fn specular_chance(&self, f0: Color, cos_o: f64) -> f64 {
+        let (_, f) = self.specular_albedo(f0, cos_o);
+        let spec = ((f.r + f.g + f.b) / 3.0).min(1.0);
+        let diffuse = (1.0 - self.metallic) * (1.0 - spec);
+        (spec / (spec + diffuse)).clamp(0.1, 1.0)
+    }
+
+    // BRDF times the cosine at `wi`, both directions in the local frame
+    fn eval_local(&self, base: Color, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let alpha = self.alpha();
+        let f0 = self.f0(base);
+        let h = (wo + wi).unit_vector();
+
+        let (compensation, reflected) = self.specular_albedo(f0, wo.z);
+
+        let f = schlick(f0, Vec3::dot(wo, h));
+        let g2 = 1.0 / (1.0 + smith_lambda(wo, alpha) + smith_lambda(wi, alpha));
+        let specular = (ggx_d(h, alpha) * g2 / (4.0 * wo.z)) * (compensation * f);
+
+        // What the coat doesn't reflect reaches the diffuse base
+        let transmitted = Color::new(1.0 - reflected.r, 1.0 - reflected.g, 1.0 - reflected.b);
+        let diffuse = ((1.0 - self.metallic) * wi.z / PI) * (transmitted * base);
+        specular + diffuse
+    }
+
+    // Density of `sample` picking `wi`, per unit solid angle
+    fn pdf_local(&self, base: Color, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let alpha = self.alpha();
+        let h = (wo + wi).unit_vector();
+        // Visible normals: D_wo(h) / (4 wo.h) = G1(wo) D(h) / (4 wo.z)
+        let g1 = 1.0 / (1.0 + smith_lambda(wo, alpha));
+        let specular = g1 * ggx_d(h, alpha) / (4.0 * wo.z);
+        let diffuse = wi.z / PI;
+        let p = self.specular_chance(self.f0(base), wo.z);
+        p * specular + (1.0 - p) * diffuse
+    }
+
+    fn sample_local(&self, base: Color, wo: Vec3) -> Vec3 {
+        if random_f64() < self.specular_chance(self.f0(base), wo.z) {
+            let h = sample_vndf(wo, self.alpha(), random_f64(), random_f64());
+            Vec3::reflect(-wo, h)
+        } else {
+            cosine_direction()
+        }
+    }
+}
+
+impl Material for Microfacet {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = Frame::new(rec.normal);
+        let wo = frame.to_local(-r_in.direction.unit_vector());
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let base = self.base_color.value(rec.u, rec.v, rec.p);
+        let wi = self.sample_local(base, wo);
+        let pdf = self.pdf_local(base, wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = (1.0 / pdf) * self.eval_local(base, wo, wi);
+        Some((weight, Ray::new(rec.p, frame.to_world(wi))))
+    }
+
+    fn lobes(&self) -> Lobes {
+        Lobes { direct_lighting: true, ..Default::default() }
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        let frame = Frame::new(rec.normal);
+        let base = self.base_color.value(rec.u, rec.v, rec.p);
+        self.eval_local(base, frame.to_local(wo), frame.to_local(wi))
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64 {
+        let frame = Frame::new(rec.normal);
+        let base = self.base_color.value(rec.u, rec.v, rec.p);
+        self.pdf_local(base, frame.to_local(wo), frame.to_local(wi))
+    }
+
+    fn emission(&self, rec: &HitRecord) -> Color {
+        self.brightness * self.base_color.value(rec.u, rec.v, rec.p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.brightness > 0.0
+    }
+}
+
+// Orthonormal frame with z along the surface normal
+struct Frame {
+    s: Vec3,
+    t: Vec3,
+    n: Vec3,
+}
+
+impl Frame {
+    fn new(n: Vec3) -> Frame {
+        let (s, t) = Vec3::orthonormal_basis(n);
+        Frame { s, t, n }
+    }
+
+    fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(v, self.s), Vec3::dot(v, self.t), Vec3::dot(v, self.n))
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x * self.s + v.y * self.t + v.z * self.n
+    }
+}
+
+fn schlick(f0: Color, cosine: f64) -> Color {
+    let w = (1.0 - cosine.clamp(0.0, 1.0)).powi(5);
+    (1.0 - w) * f0 + w * Color::new(1.0, 1.0, 1.0)
+}
+
+// GGX (Trowbridge-Reitz) normal distribution, for a local half vector
+fn ggx_d(h: Vec3, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let d = h.z * h.z * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// Smith's Lambda for GGX; G1 = 1 / (1 + Lambda)
+fn smith_lambda(w: Vec3, alpha: f64) -> f64 {
+    let cos2 = w.z * w.z;
+    if cos2 <= 0.0 {
+        return f64::INFINITY;
+    }
+    let tan2 = ((1.0 - cos2) / cos2).max(0.0);
+    0.5 * ((1.0 + alpha * alpha * tan2).sqrt() - 1.0)
+}
+
+// Half vector from the normals visible from `wo` (Heitz 2018): the
+// hemisphere is stretched to the roughness, a point is picked on the
+// projected disk it presents to `wo`, and the result is stretched back
+fn sample_vndf(wo: Vec3, alpha: f64, u1: f64, u2: f64) -> Vec3 {
+    let vh = Vec3::new(alpha * wo.x, alpha * wo.y, wo.z).unit_vector();
+    let len2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if len2 > 0.0 { Vec3::new(-vh.y, vh.x, 0.0) / len2.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+    let t2 = Vec3::cross(vh, t1);
+
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+    Vec3::new(alpha * nh.x, alpha * nh.y, nh.z.max(0.0)).unit_vector()
+}
+
+fn cosine_direction() -> Vec3 {
+    let r = random_f64().sqrt();
+    let phi = 2.0 * PI * random_f64();
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r * r).max(0.0).sqrt())
+}
+
+const ALBEDO_SIZE: usize = 32;
+
+// How much light a GGX surface reflects in one bounce, by the cosine of the
+// view angle and roughness, split as scale * F0 + bias (so the Schlick
+// Fresnel can be applied to any F0 afterwards). Tabulated once by
+// integrating over stratified visible-normal samples, then interpolated.
+fn directional_albedo(cos_o: f64, roughness: f64) -> (f64, f64) {
+    static TABLE: OnceLock<Vec<(f64, f64)>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let n = 16;
+        let mut table = Vec::with_capacity(ALBEDO_SIZE * ALBEDO_SIZE);
+        for j in 0..ALBEDO_SIZE {
+            let alpha = ((j as f64 / (ALBEDO_SIZE - 1) as f64).powi(2)).max(MIN_ALPHA);
+            for i in 0..ALBEDO_SIZE {
+                let cos_o = (i as f64 / (ALBEDO_SIZE - 1) as f64).max(1e-3);
+                let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+                let lambda_o = smith_lambda(wo, alpha);
+                // With VNDF sampling each sample's weight is G2 / G1(wo)
+                let (mut scale, mut bias) = (0.0, 0.0);
+                for a in 0..n {
+                    for b in 0..n {
+                        let h = sample_vndf(wo, alpha, (a as f64 + 0.5) / n as f64, (b as f64 + 0.5) / n as f64);
+                        let wi = Vec3::reflect(-wo, h);
+                        if wi.z > 0.0 {
+                            let weight = (1.0 + lambda_o) / (1.0 + lambda_o + smith_lambda(wi, alpha));
+                            let w = (1.0 - Vec3::dot(wo, h).clamp(0.0, 1.0)).powi(5);
+                            scale += (1.0 - w) * weight;
+                            bias += w * weight;
+                        }
+                    }
+                }
+                table.push((scale / (n * n) as f64, bias / (n * n) as f64));
+            }
+        }
+        table
+    });
+
+    // Bilinear lookup
+    let scale = (ALBEDO_SIZE - 1) as f64;
+    let x = cos_o.clamp(0.0, 1.0) * scale;
+    let y = roughness.clamp(0.0, 1.0) * scale;
+    let (i, j) = ((x as usize).min(ALBEDO_SIZE - 2), (y as usize).min(ALBEDO_SIZE - 2));
+    let (fx, fy) = (x - i as f64, y - j as f64);
+    let lerp = |a: (f64, f64), b: (f64, f64), t: f64| ((1.0 - t) * a.0 + t * b.0, (1.0 - t) * a.1 + t * b.1);
+    let at = |i: usize, j: usize| table[j * ALBEDO_SIZE + i];
+    lerp(lerp(at(i, j), at(i + 1, j), fx), lerp(at(i, j + 1), at(i + 1, j + 1), fx), fy)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::SolidColor;
+
+    fn solid(base: Color, roughness: f64, metallic: f64) -> Microfacet {
+        Microfacet::from_texture(Arc::new(SolidColor::new(base)), roughness, metallic, 0.0)
+    }
+
+    // Average weight of sampled bounces for light arriving along `cos_o`
+    fn albedo(mat: &Microfacet, cos_o: f64) -> Color {
+        let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+        let base = Color::new(1.0, 1.0, 1.0);
+        let n = 100_000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let wi = mat.sample_local(base, wo);
+            let pdf = mat.pdf_local(base, wo, wi);
+            if pdf > 0.0 {
+                sum += (1.0 / pdf) * mat.eval_local(base, wo, wi);
+            }
+        }
+        (1.0 / n as f64) * sum
+    }
+
+    #[test]
+    fn test_white_furnace() {
+        // White metal and white plastic both reflect everything that reaches
+        // them, whatever the roughness
+        for roughness in [0.05, 0.3, 0.6, 1.0] {
+            for cos_o in [0.2, 0.6, 1.0] {
+                let metal = albedo(&solid(Color::new(1.0, 1.0, 1.0), roughness, 1.0), cos_o);
+                assert!((metal.r - 1.0).abs() < 0.03, "metal roughness {roughness}, cos {cos_o}: {}", metal.r);
+                let plastic = albedo(&solid(Color::new(1.0, 1.0, 1.0), roughness, 0.0), cos_o);
+                assert!((plastic.r - 1.0).abs() < 0.03, "plastic roughness {roughness}, cos {cos_o}: {}", plastic.r);
+            }
+        }
+    }
+
+    #[test]
+    fn test_vndf_sampling_matches_pdf() {
+        // Histogram of sampled directions against the pdf, over bands of cos theta
+        let mat = solid(Color::new(0.5, 0.5, 0.5), 0.4, 1.0);
+        let base = Color::new(0.5, 0.5, 0.5);
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        let bands = 8;
+        let n = 200_000;
+        let mut counts = vec![0.0; bands];
+        for _ in 0..n {
+            let wi = mat.sample_local(base, wo);
+            if wi.z > 0.0 {
+                counts[((wi.z * bands as f64) as usize).min(bands - 1)] += 1.0 / n as f64;
+            }
+        }
+        // Integrate the pdf over each band numerically
+        let steps = 400;
+        for (band, count) in counts.iter().enumerate() {
+            let mut expected = 0.0;
+            for a in 0..steps {
+                for b in 0..steps {
+                    let cos = (band as f64 + (a as f64 + 0.5) / steps as f64) / bands as f64;
+                    let phi = 2.0 * PI * (b as f64 + 0.5) / steps as f64;
+                    let sin = (1.0 - cos * cos).sqrt();
+                    let wi = Vec3::new(sin * phi.cos(), sin * phi.sin(), cos);
+                    expected += mat.pdf_local(base, wo, wi) * (2.0 * PI / steps as f64) / (bands * steps) as f64;
+                }
+            }
+            assert!((count - expected).abs() < 0.01, "band {band}: sampled {count}, pdf {expected}");
+        }
+    }
+}
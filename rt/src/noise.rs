@@ -0,0 +1,158 @@
+use serde::Deserialize;
+use crate::color::Color;
+use crate::texture::Texture;
+use crate::util;
+use crate::vec3::{Point3, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient (Perlin) noise: random unit gradients on the integer lattice,
+// blended with a smooth falloff. Zero at every lattice point and roughly
+// within [-1, 1] in between.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    // The whole table is drawn from util::rng(seed), so a seed always gives the same pattern
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = util::rng(seed);
+        let mut coord = || 2.0 * rng.f64() - 1.0;
+        let gradients = (0..POINT_COUNT).map(|_| Vec3::new(coord(), coord(), coord()).unit_vector()).collect();
+        let (perm_x, perm_y, perm_z) = (permutation(&mut rng), permutation(&mut rng), permutation(&mut rng));
+        Perlin { gradients, perm_x, perm_y, perm_z }
+    }
+
+    pub fn noise(&self, p: Point3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let mut accum = 0.0;
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (uu, vv, ww) = (smooth(u), smooth(v), smooth(w));
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize];
+                    let (a, b, c) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - a, v - b, w - c);
+                    accum += (a * uu + (1.0 - a) * (1.0 - uu))
+                        * (b * vv + (1.0 - b) * (1.0 - vv))
+                        * (c * ww + (1.0 - c) * (1.0 - ww))
+                        * Vec3::dot(self.gradients[index], weight);
+                }
+            }
+        }
+        accum
+    }
+
+    // Sum of |noise| over octaves of doubling frequency and halving weight; never negative
+    pub fn turbulence(&self, p: Point3, octaves: usize) -> f64 {
+        let mut accum = 0.0;
+        let (mut p, mut weight) = (p, 1.0);
+        for _ in 0..octaves {
+            accum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum
+    }
+
+    // Fractal Brownian motion: like turbulence but keeps the sign, for
+    // smoother, terrain-like variation around zero
+    pub fn fbm(&self, p: Point3, octaves: usize) -> f64 {
+        let mut accum = 0.0;
+        let (mut p, mut weight) = (p, 1.0);
+        for _ in 0..octaves {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum
+    }
+}
+
+// Shuffled 0..POINT_COUNT
+fn permutation(rng: &mut fastrand::Rng) -> Vec<usize> {
+    let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+        let target = rng.usize(0..=i);
+        p.swap(i, target);
+    }
+    p
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseKind {
+    Perlin,
+    Turbulence,
+    Fbm,
+    // Veins along z, bent by turbulence
+    #[default]
+    Marble,
+}
+
+// Blends between two colours with a noise pattern evaluated at the hit point
+pub struct NoiseTexture {
+    perlin: Perlin,
+    kind: NoiseKind,
+    scale: f64,
+    octaves: usize,
+    low: Color,
+    high: Color,
+}
+
+impl NoiseTexture {
+    pub fn new(perlin: Perlin, kind: NoiseKind, scale: f64, octaves: usize, low: Color, high: Color) -> NoiseTexture {
+        NoiseTexture { perlin, kind, scale, octaves, low, high }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let p = self.scale * p;
+        let t = match self.kind {
+            NoiseKind::Perlin => 0.5 * (1.0 + self.perlin.noise(p)),
+            NoiseKind::Turbulence => self.perlin.turbulence(p, self.octaves),
+            NoiseKind::Fbm => 0.5 * (1.0 + self.perlin.fbm(p, self.octaves)),
+            NoiseKind::Marble => 0.5 * (1.0 + (p.z + 10.0 * self.perlin.turbulence(p, self.octaves)).sin()),
+        };
+        let t = t.clamp(0.0, 1.0);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::random_f64_range;
+
+    #[test]
+    fn test_perlin() {
+        let a = Perlin::new(42);
+        let b = Perlin::new(42);
+        let other = Perlin::new(43);
+        let p = Point3::new(0.5, 1.25, -2.75);
+        assert_ne!(a.noise(p), other.noise(p));
+        for _ in 0..1000 {
+            let p = Vec3::random_range(-20.0, 20.0);
+            assert_eq!(a.noise(p), b.noise(p));
+            assert!(a.noise(p).abs() <= 1.0);
+            assert!(a.turbulence(p, 7) >= 0.0);
+        }
+        // Gradient noise vanishes on the lattice
+        let lattice = Point3::new(random_f64_range(-9.0, 9.0).floor(), 3.0, -4.0);
+        assert_eq!(a.noise(lattice), 0.0);
+
+        let fbm = Perlin::new(7);
+        let p = Point3::new(random_f64_range(0.0, 1.0), 0.5, 0.25);
+        assert_eq!(fbm.fbm(p, 1), fbm.noise(p));
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::color::Color;
+use crate::material::{Material, Lambertian, Metal, Dialectric, DiffuseLight};
+use crate::triangle::MeshData;
+use crate::vec3::{Point3, Vec3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, err: std::io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, err } => write!(f, "{}: {err}", path.display()),
+            ObjError::Parse { path, line, message } => write!(f, "{}: line {line}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// Faces of one group that share a material
+pub struct ObjMesh {
+    pub material: Option<String>,
+    pub data: MeshData,
+}
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, MtlMaterial>,
+}
+
+// The MTL fields we understand; everything else in the file is ignored
+#[derive(Clone, Copy)]
+pub struct MtlMaterial {
+    pub kd: Color,  // diffuse colour
+    pub ks: Color,  // specular colour
+    pub ns: f64,    // specular exponent
+    pub ni: f64,    // index of refraction
+    pub d: f64,     // dissolve (1 = opaque)
+    pub ke: Color,  // emission
+}
+
+impl Default for MtlMaterial {
+    fn default() -> MtlMaterial {
+        MtlMaterial {
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.5,
+            d: 1.0,
+            ke: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+fn max_component(c: Color) -> f64 {
+    c.r.max(c.g).max(c.b)
+}
+
+impl MtlMaterial {
+    // Maps onto the closest material we have: anything with a Ke glows in
+    // exactly that colour, see-through surfaces become glass, surfaces whose
+    // specular colour outweighs the diffuse one become metal (rougher for a
+    // lower Ns), and everything else is Lambertian.
+    pub fn to_material(self) -> Arc<dyn Material> {
+        if max_component(self.ke) > 0.0 {
+            Arc::new(DiffuseLight::new(self.ke, 1.0))
+        } else if self.d < 1.0 {
+            Arc::new(Dialectric::new(self.ni, 0.0))
+        } else if max_component(self.ks) > max_component(self.kd) {
+            let fuzz = (2.0 / (self.ns.max(0.0) + 2.0)).sqrt();
+            Arc::new(Metal::new(self.ks, fuzz, 0.0))
+        } else {
+            Arc::new(Lambertian::new(self.kd, 0.0))
+        }
+    }
+}
+
+// Reads an OBJ file and every MTL library it references
+pub fn load(path: &Path) -> Result<ObjModel, ObjError> {
+    let read = |path: &Path| std::fs::read_to_string(path)
+        .map_err(|err| ObjError::Io { path: path.to_path_buf(), err });
+
+    let src = read(path)?;
+    let parsed = parse_obj(&src, path)?;
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut materials = HashMap::new();
+    for lib in &parsed.mtllibs {
+        let lib_path = dir.join(lib);
+        materials.extend(parse_mtl(&read(&lib_path)?, &lib_path)?);
+    }
+
+    for (name, line) in &parsed.material_uses {
+        if !materials.contains_key(name) {
+            return Err(ObjError::Parse { path: path.to_path_buf(), line: *line, message: format!("unknown material `{name}`") });
+        }
+    }
+    Ok(ObjModel { meshes: parsed.meshes, materials })
+}
+
+struct ParsedObj {
+    meshes: Vec<ObjMesh>,
+    mtllibs: Vec<String>,
+    // Each `usemtl` name with the line it first appeared on
+    material_uses: Vec<(String, usize)>,
+}
+
+// Collects faces for one (group, material) pair, welding identical corners
+struct MeshBuilder {
+    mesh: ObjMesh,
+    corners: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    has_normals: bool,
+    has_uvs: bool,
+}
+
+impl MeshBuilder {
+    fn corner(&mut self, key: (usize, Option<usize>, Option<usize>), positions: &[Point3],
+              uvs: &[(f64, f64)], normals: &[Vec3]) -> usize {
+        if let Some(&index) = self.corners.get(&key) {
+            return index;
+        }
+        let (v, vt, vn) = key;
+        let data = &mut self.mesh.data;
+        let index = data.positions.len();
+        data.positions.push(positions[v]);
+        // A mesh only keeps normals/uvs if every corner has them
+        match vn {
+            Some(vn) => data.normals.push(normals[vn]),
+            None => self.has_normals = false,
+        }
+        match vt {
+            Some(vt) => data.uvs.push(uvs[vt]),
+            None => self.has_uvs = false,
+        }
+        self.corners.insert(key, index);
+        index
+    }
+
+    fn finish(mut self) -> ObjMesh {
+        if !self.has_normals {
+            self.mesh.data.normals.clear();
+        }
+        if !self.has_uvs {
+            self.mesh.data.uvs.clear();
+        }
+        self.mesh
+    }
+}
+
+fn parse_obj(src: &str, path: &Path) -> Result<ParsedObj, ObjError> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut builders: Vec<MeshBuilder> = Vec::new();
+    let mut builder_index: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut group = String::from("default");
+    let mut material: Option<String> = None;
+    let mut mtllibs = Vec::new();
+    let mut material_uses: Vec<(String, usize)> = Vec::new();
+
+    for (line_index, line) in src.lines().enumerate() {
+        let line_no = line_index + 1;
+        let err = |message: String| ObjError::Parse { path: path.to_path_buf(), line: line_no, message };
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = floats::<3>(&args, 3).map_err(err)?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = floats::<3>(&args, 3).map_err(err)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = floats::<2>(&args, 1).map_err(err)?;
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(format!("face needs at least 3 vertices, got {}", args.len())));
+                }
+                let key = (group.clone(), material.clone());
+                let index = *builder_index.entry(key).or_insert_with(|| {
+                    builders.push(MeshBuilder {
+                        mesh: ObjMesh { material: material.clone(), data: MeshData::default() },
+                        corners: HashMap::new(),
+                        has_normals: true,
+                        has_uvs: true,
+                    });
+                    builders.len() - 1
+                });
+
+                let mut corners = Vec::with_capacity(args.len());
+                for vertex in &args {
+                    let key = face_vertex(vertex, positions.len(), uvs.len(), normals.len()).map_err(err)?;
+                    corners.push(builders[index].corner(key, &positions, &uvs, &normals));
+                }
+                // Fan triangulation, fine for the convex polygons OBJ exporters write
+                let indices = &mut builders[index].mesh.data.indices;
+                for i in 1..corners.len() - 1 {
+                    indices.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                group = if args.is_empty() { String::from("default") } else { args.join(" ") };
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if name.is_empty() {
+                    return Err(err("usemtl needs a material name".to_string()));
+                }
+                if !material_uses.iter().any(|(n, _)| *n == name) {
+                    material_uses.push((name.clone(), line_no));
+                }
+                material = Some(name);
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(err("mtllib needs a file name".to_string()));
+                }
+                mtllibs.push(args.join(" "));
+            }
+            // Smoothing groups, free-form geometry, lines and points are ignored
+            _ => {}
+        }
+    }
+
+    let meshes = builders.into_iter().map(MeshBuilder::finish).collect();
+    Ok(ParsedObj { meshes, mtllibs, material_uses })
+}
+
+// Parses "v", "v/vt", "v//vn" or "v/vt/vn" into zero-based indices.
+// OBJ indices start at 1, and negative ones count back from the latest element.
+fn face_vertex(token: &str, n_positions: usize, n_uvs: usize, n_normals: usize)
+    -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let resolve = |s: &str, len: usize, what: &str| -> Result<usize, String> {
+        let i: i64 = s.parse().map_err(|_| format!("invalid {what} index `{s}` in `{token}`"))?;
+        let index = if i > 0 { i - 1 } else { len as i64 + i };
+        if i == 0 || index < 0 || index >= len as i64 {
+            return Err(format!("{what} index {i} is out of range ({len} defined so far)"));
+        }
+        Ok(index as usize)
+    };
+
+    let mut parts = token.split('/');
+    let v = resolve(parts.next().unwrap_or(""), n_positions, "vertex")?;
+    let vt = match parts.next() {
+        Some("") | None => None,
+        Some(s) => Some(resolve(s, n_uvs, "texture coordinate")?),
+    };
+    let vn = match parts.next() {
+        Some("") | None => None,
+        Some(s) => Some(resolve(s, n_normals, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex `{token}`"));
+    }
+    Ok((v, vt, vn))
+}
+
+// Reads N numbers where the first `required` must be present and the rest default to 0
+fn floats<const N: usize>(args: &[&str], required: usize) -> Result<[f64; N], String> {
+    if args.len() < required {
+        return Err(format!("expected at least {required} numbers, got {}", args.len()));
+    }
+    let mut out = [0.0; N];
+    for (slot, arg) in out.iter_mut().zip(args) {
+        *slot = arg.parse().map_err(|_| format!("invalid number `{arg}`"))?;
+    }
+    Ok(out)
+}
+
+fn parse_mtl(src: &str, path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (line_index, line) in src.lines().enumerate() {
+        let err = |message: String| ObjError::Parse { path: path.to_path_buf(), line: line_index + 1, message };
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(err("newmtl needs a material name".to_string()));
+            }
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl);
+            }
+            current = Some((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        let color = |args: &[&str]| -> Result<Color, String> {
+            // A single value means grey
+            let [r, g, b] = floats::<3>(args, 1)?;
+            Ok(if args.len() == 1 { Color::new(r, r, r) } else { Color::new(r, g, b) })
+        };
+        let known = ["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr"];
+        if !known.contains(&keyword) {
+            continue;
+        }
+        let Some((_, mtl)) = current.as_mut() else {
+            return Err(err(format!("`{keyword}` before any newmtl")));
+        };
+        match keyword {
+            "Kd" => mtl.kd = color(&args).map_err(err)?,
+            "Ks" => mtl.ks = color(&args).map_err(err)?,
+            "Ke" => mtl.ke = color(&args).map_err(err)?,
+            "Ns" => mtl.ns = floats::<1>(&args, 1).map_err(err)?[0],
+            "Ni" => mtl.ni = floats::<1>(&args, 1).map_err(err)?[0],
+            "d" => mtl.d = floats::<1>(&args, 1).map_err(err)?[0],
+            // Transparency, the inverse of dissolve
+            _ => mtl.d = 1.0 - floats::<1>(&args, 1).map_err(err)?[0],
+        }
+    }
+    if let Some((name, mtl)) = current {
+        materials.insert(name, mtl);
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable::HitRecord;
+
+    const CUBE_CORNER: &str = "
+# three faces of a cube as quads, one with a negative index
+mtllib parts.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 -1
+g front
+usemtl red
+f 1/1/1 4/4/1 3/3/1 2/2/1
+g side
+f 1 5 -4
+usemtl glass
+f 1//1 2//1 5//1
+";
+
+    fn parse(src: &str) -> Result<ParsedObj, ObjError> {
+        parse_obj(src, Path::new("test.obj"))
+    }
+
+    fn line_of(err: ObjError) -> usize {
+        match err {
+            ObjError::Parse { line, .. } => line,
+            err => panic!("unexpected error {err}"),
+        }
+    }
+
+    #[test]
+    fn test_groups_materials_and_triangulation() {
+        let parsed = parse(CUBE_CORNER).unwrap();
+        assert_eq!(parsed.mtllibs, ["parts.mtl"]);
+        assert_eq!(parsed.material_uses, [("red".to_string(), 15), ("glass".to_string(), 19)]);
+
+        let meshes = &parsed.meshes;
+        assert_eq!(meshes.len(), 3);
+        // `g side` starts a new mesh even though the material stays the same
+        assert_eq!(meshes[0].material.as_deref(), Some("red"));
+        assert_eq!(meshes[1].material.as_deref(), Some("red"));
+        // The quad is split into two triangles sharing 4 welded corners
+        assert_eq!(meshes[0].data.indices, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(meshes[0].data.positions.len(), 4);
+        assert_eq!(meshes[0].data.normals.len(), 4);
+        assert_eq!(meshes[0].data.uvs[2], (1.0, 1.0));
+        // -4 counts back from the fifth vertex to the second
+        assert_eq!(meshes[1].data.positions[2], Point3::new(1.0, 0.0, 0.0));
+        assert!(meshes[1].data.normals.is_empty() && meshes[1].data.uvs.is_empty());
+        assert_eq!(meshes[2].data.normals.len(), 3);
+        assert!(meshes[2].data.uvs.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_obj_reports_line() {
+        assert_eq!(line_of(parse("v 0 0 0\nv 1 0\n").err().unwrap()), 2);
+        assert_eq!(line_of(parse("v 0 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 4\n").err().unwrap()), 5);
+        assert_eq!(line_of(parse("v 0 0 0\nv 1 0 0\nf 1 2\n").err().unwrap()), 3);
+        assert_eq!(line_of(parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1/x 2 3\n").err().unwrap()), 4);
+        assert_eq!(line_of(parse("v 0 zero 0\n").err().unwrap()), 1);
+        assert_eq!(line_of(parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 0 1 2\n").err().unwrap()), 4);
+    }
+
+    #[test]
+    fn test_mtl_fields() {
+        let src = "newmtl red\nKd 0.8 0.1 0.1\nKe 0.4 0 0\nillum 2\n\nnewmtl glass\nNi 1.45\nd 0.2\n";
+        let materials = parse_mtl(src, Path::new("test.mtl")).unwrap();
+        assert_eq!(materials["red"].kd.g, 0.1);
+        assert_eq!(materials["red"].ke.r, 0.4);
+        assert_eq!(materials["glass"].ni, 1.45);
+        assert_eq!(materials["glass"].d, 0.2);
+
+        // Ke alone is enough to make a light, in Ke's own colour
+        let lamp = parse_mtl("newmtl lamp\nKd 0 0 0\nKe 4 3 2\n", Path::new("test.mtl")).unwrap()["lamp"].to_material();
+        let rec = HitRecord::new(1.0, Point3::zero(), Vec3::new(0.0, 1.0, 0.0), &*lamp);
+        let emitted = lamp.emission(&rec);
+        assert_eq!((emitted.r, emitted.g, emitted.b), (4.0, 3.0, 2.0));
+        assert!(lamp.is_emissive());
+        assert!(!materials["glass"].to_material().is_emissive());
+
+        let err = parse_mtl("Kd 1 1 1\n", Path::new("test.mtl")).err().unwrap();
+        assert_eq!(line_of(err), 1);
+        let err = parse_mtl("newmtl a\nNs shiny\n", Path::new("test.mtl")).err().unwrap();
+        assert_eq!(line_of(err), 2);
+    }
+}
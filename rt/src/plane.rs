@@ -1,27 +1,33 @@
+use std::sync::Arc;
 use crate::vec3::{Point3, Vec3};
 use crate::hittable::{Hittable, HitRecord};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
 
-pub struct Plane<'a> {
+pub struct Plane {
     pub point: Point3,    // A point on the plane
     pub normal: Vec3,    // Normal vector of the plane
-    pub mat: Box<dyn Material + 'a>
+    pub mat: Arc<dyn Material>,
+    // Directions of u and v along the plane
+    axes: (Vec3, Vec3),
 }
 
-impl <'a>Plane<'a> {
-    pub fn new(point: Point3, normal: Vec3, mat: impl Material + 'a) -> Plane<'a> {
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Material>) -> Plane {
+        let normal = normal.unit_vector();  // Ensure normal is normalized
         Plane{ 
             point, 
-            normal: normal.unit_vector(),  // Ensure normal is normalized
-            mat: Box::new(mat) 
+            normal,
+            mat,
+            axes: Vec3::orthonormal_basis(normal),
         }
     }
 }
 
-impl <'a>Hittable for Plane<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let denom = Vec3::dot(self.normal, r.direction);
         
         // Ray is parallel to plane (no intersection)
@@ -40,8 +46,17 @@ impl <'a>Hittable for Plane<'a> {
         
         let p = r.at(t);
         let mut rec = HitRecord::new(t, p, self.normal, &*self.mat);
+        // uv is the distance from `point` along the two axes, so a uv checker
+        // with one column and row gives unit squares
+        let offset = p - self.point;
+        (rec.u, rec.v) = (Vec3::dot(offset, self.axes.0), Vec3::dot(offset, self.axes.1));
         rec.set_face_normal(r);
         Some(rec)
     }
+
+    // A plane extends forever, so it has no box
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
 }
 
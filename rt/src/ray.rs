@@ -1,15 +1,35 @@
 use crate::vec3::{Point3, Vec3};
+use crate::color::Color;
+use crate::spectrum;
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    // Wavelength in nm this camera sample stands for; only dispersive
+    // materials look at it
+    pub wavelength: f64,
+    // Set once something along the path has depended on `wavelength`, so
+    // the path only carries light of that wavelength from then on
+    pub monochromatic: bool,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray {origin, direction}
+        Ray {origin, direction, wavelength: 550.0, monochromatic: false}
     }
     pub fn at(&self, t: f64) -> Point3 {
         self.origin + t * self.direction
     }
+    // How much of an RGB reflectance or emitter this ray's light sees: all
+    // of it, or on a monochromatic path the value of its upsampled spectrum
+    // at the ray's wavelength, in every channel
+    pub fn carried(&self, c: Color) -> Color {
+        if self.monochromatic {
+            let v = spectrum::rgb_to_spectrum(c, self.wavelength);
+            Color::new(v, v, v)
+        } else {
+            c
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use crate::vec3::{Point3, Vec3};
+use crate::hittable::{Hittable, HitRecord, LightSample};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::util::random_f64;
+
+// Parallelogram spanned by the edges `u` and `v` from `corner` (a rectangle
+// when they are perpendicular). The front faces along u x v.
+pub struct Rect {
+    pub corner: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    normal: Vec3,
+    w: Vec3,  // n / (n . n), turns a point on the plane into (alpha, beta)
+    area: f64,
+    pub mat: Arc<dyn Material>
+}
+
+impl Rect {
+    pub fn new(corner: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Rect {
+        let n = Vec3::cross(u, v);
+        Rect {
+            corner,
+            u,
+            v,
+            normal: n.unit_vector(),
+            w: n / n.length_squared(),
+            area: n.length(),
+            mat
+        }
+    }
+}
+
+impl Hittable for Rect {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = Vec3::dot(self.normal, r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = Vec3::dot(self.corner - r.origin, self.normal) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        // Position on the plane in terms of the two edges
+        let p = r.at(t);
+        let planar = p - self.corner;
+        let alpha = Vec3::dot(self.w, Vec3::cross(planar, self.v));
+        let beta = Vec3::dot(self.w, Vec3::cross(self.u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(t, p, self.normal, &*self.mat);
+        (rec.u, rec.v) = (alpha, beta);
+        rec.set_face_normal(r);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let diagonal = Aabb::from_points(self.corner, self.corner + self.u + self.v);
+        let other = Aabb::from_points(self.corner + self.u, self.corner + self.v);
+        Some(Aabb::surrounding(&diagonal, &other))
+    }
+
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let p = self.corner + random_f64() * self.u + random_f64() * self.v;
+        LightSample::from_area(origin, p, self.normal, self.area)
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        LightSample::area_pdf(self, r, ray_t, self.area)
+    }
+}
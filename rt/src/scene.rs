@@ -0,0 +1,927 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use serde::Deserialize;
+use toml::Spanned;
+use glam::DMat4;
+use crate::camera::Camera;
+use crate::integrator::IntegratorKind;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::light::Light;
+use crate::material::{Material, MaterialLibrary, Lambertian, Metal, Dialectric, Dispersion, DiffuseLight};
+use crate::microfacet::Microfacet;
+use crate::sphere::Sphere;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::plane::Plane;
+use crate::rect::Rect;
+use crate::disk::Disk;
+use crate::triangle::{Triangle, TriangleMesh, MeshData};
+use crate::texture::{Texture, SolidColor, CheckerTexture, UvCheckerTexture};
+use crate::image_texture::{ImageTexture, WrapMode, Filter};
+use crate::noise::{Perlin, NoiseKind, NoiseTexture};
+use crate::color::Color;
+use crate::vec3::Vec3;
+use crate::obj;
+use crate::transform::{self, Transform};
+use crate::instance::Instance;
+use crate::bvh::BvhNode;
+
+// Bump when the file layout changes in a way older files can't be read with
+pub const SCENE_VERSION: u32 = 1;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList,
+    pub lights: Vec<Light>,
+    pub materials: MaterialLibrary,
+}
+
+impl Scene {
+    pub fn new(camera: Camera) -> Scene {
+        Scene { camera, world: HittableList::new(), lights: Vec::new(), materials: MaterialLibrary::new() }
+    }
+
+    pub fn add(&mut self, object: impl Hittable + 'static) {
+        self.world.add(object);
+    }
+
+    // An emissive object: it's part of the world and also sampled as an area light
+    pub fn add_light(&mut self, object: impl Hittable + 'static) {
+        let object: Arc<dyn Hittable> = Arc::new(object);
+        self.world.add(Arc::clone(&object));
+        self.lights.push(Light::Area(object));
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse { line: usize, column: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "{err}"),
+            SceneError::Parse { line, column, message } => write!(f, "line {line}, column {column}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> SceneError {
+        SceneError::Io(err)
+    }
+}
+
+// ---- file layout ----
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    version: Spanned<u32>,
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    // Shapes that aren't drawn themselves, only through instances
+    #[serde(default)]
+    geometry: HashMap<String, Spanned<ObjectDesc>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDesc>>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    // Spanned so values the renderer can't use are reported where they are
+    aspect_ratio: Option<Spanned<f64>>,
+    image_width: Option<Spanned<usize>>,
+    samples_per_pixel: Option<Spanned<usize>>,
+    max_depth: Option<usize>,
+    vfov: Option<f64>,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    defocus_angle: Option<f64>,
+    focus_dist: Option<f64>,
+    spectral: Option<bool>,
+    integrator: Option<IntegratorKind>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: [f64; 3] },
+    Checker { scale: f64, even: [f64; 3], odd: [f64; 3] },
+    UvChecker { columns: f64, rows: f64, even: [f64; 3], odd: [f64; 3] },
+    // PNG or PPM file, relative to the scene file
+    Image { path: String, #[serde(default)] wrap: WrapMode, #[serde(default)] filter: Filter },
+    // Procedural pattern blending from `low` to `high`. The pattern depends only
+    // on `seed`, which defaults to 0, so a file renders the same every run.
+    Noise {
+        #[serde(default)]
+        kind: NoiseKind,
+        #[serde(default = "one")]
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: usize,
+        #[serde(default = "black")]
+        low: [f64; 3],
+        #[serde(default = "white")]
+        high: [f64; 3],
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+// A plain colour, or the name of a texture
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlbedoDesc {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: AlbedoDesc, #[serde(default)] brightness: f64 },
+    Metal { albedo: AlbedoDesc, #[serde(default)] fuzz: f64, #[serde(default)] brightness: f64 },
+    Dialectric {
+        refraction_index: Option<f64>,
+        dispersion: Option<DispersionDesc>,
+        #[serde(default = "white")] tint: [f64; 3],
+        #[serde(default)] absorption: [f64; 3],
+        #[serde(default)] brightness: f64,
+    },
+    Microfacet {
+        base_color: AlbedoDesc,
+        roughness: f64,
+        #[serde(default)] metallic: f64,
+        #[serde(default)] brightness: f64,
+    },
+    Light { #[serde(default = "white")] color: [f64; 3], #[serde(default = "one")] intensity: f64 },
+}
+
+// Wavelength-dependent refractive index, with wavelengths in micrometres
+#[derive(Deserialize)]
+#[serde(tag = "model", rename_all = "lowercase", deny_unknown_fields)]
+enum DispersionDesc {
+    Cauchy { a: f64, b: f64 },
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+// Every object also takes an optional `transform` list
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere { center: [f64; 3], radius: f64, material: String, #[serde(default)] transform: Vec<TransformDesc> },
+    Cube {
+        center: Option<[f64; 3]>,
+        size: Option<f64>,
+        min: Option<[f64; 3]>,
+        max: Option<[f64; 3]>,
+        material: String,
+        #[serde(default)]
+        transform: Vec<TransformDesc>,
+    },
+    Cylinder {
+        base: Option<[f64; 3]>,
+        top: Option<[f64; 3]>,
+        center: Option<[f64; 3]>,
+        height: Option<f64>,
+        radius: f64,
+        #[serde(default = "yes")]
+        capped: bool,
+        material: String,
+        #[serde(default)]
+        transform: Vec<TransformDesc>,
+    },
+    Plane { point: [f64; 3], normal: [f64; 3], material: String, #[serde(default)] transform: Vec<TransformDesc> },
+    Rect { corner: [f64; 3], u: [f64; 3], v: [f64; 3], material: String, #[serde(default)] transform: Vec<TransformDesc> },
+    Disk { center: [f64; 3], normal: [f64; 3], radius: f64, material: String, #[serde(default)] transform: Vec<TransformDesc> },
+    Triangle { vertices: [[f64; 3]; 3], material: String, #[serde(default)] transform: Vec<TransformDesc> },
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        #[serde(default)]
+        normals: Vec<[f64; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f64; 2]>,
+        indices: Vec<[usize; 3]>,
+        material: String,
+        #[serde(default)]
+        transform: Vec<TransformDesc>,
+    },
+    // Wavefront OBJ file, relative to the scene file. Without `material` the
+    // faces use the MTL materials they were assigned.
+    Obj { path: String, material: Option<String>, #[serde(default)] transform: Vec<TransformDesc> },
+    // A placement of an entry from [geometry]. With `material` every part of
+    // it uses that material instead of its own.
+    Instance { geometry: String, material: Option<String>, #[serde(default)] transform: Vec<TransformDesc> },
+}
+
+impl ObjectDesc {
+    fn transform(&self) -> &[TransformDesc] {
+        match self {
+            ObjectDesc::Sphere { transform, .. }
+            | ObjectDesc::Cube { transform, .. }
+            | ObjectDesc::Cylinder { transform, .. }
+            | ObjectDesc::Plane { transform, .. }
+            | ObjectDesc::Rect { transform, .. }
+            | ObjectDesc::Disk { transform, .. }
+            | ObjectDesc::Triangle { transform, .. }
+            | ObjectDesc::Mesh { transform, .. }
+            | ObjectDesc::Obj { transform, .. }
+            | ObjectDesc::Instance { transform, .. } => transform,
+        }
+    }
+}
+
+// One step of an object's `transform`, applied in the order listed
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDesc {
+    Translate([f64; 3]),
+    Rotate { axis: [f64; 3], degrees: f64 },
+    Scale(ScaleDesc),
+    LookAt { from: [f64; 3], to: [f64; 3], #[serde(default = "up")] up: [f64; 3] },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f64),
+    Axes([f64; 3]),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDesc {
+    position: [f64; 3],
+    #[serde(default = "white")]
+    color: [f64; 3],
+    #[serde(default = "one")]
+    intensity: f64,
+}
+
+fn white() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn black() -> [f64; 3] {
+    [0.0, 0.0, 0.0]
+}
+
+fn default_octaves() -> usize {
+    7
+}
+
+fn up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+fn color(v: [f64; 3]) -> Color {
+    Color::new(v[0], v[1], v[2])
+}
+
+// ---- loading ----
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)?;
+        Scene::parse(&src, path.parent().unwrap_or(Path::new("")))
+    }
+
+    // Relative paths inside the scene, such as OBJ files, are resolved against `dir`
+    pub fn parse(src: &str, dir: &Path) -> Result<Scene, SceneError> {
+        let error_at = |span: Option<Range<usize>>, message: String| {
+            let (line, column) = line_column(src, span.map_or(0, |s| s.start));
+            SceneError::Parse { line, column, message }
+        };
+
+        let file: SceneFile = toml::from_str(src)
+            .map_err(|err| error_at(err.span(), err.message().to_string()))?;
+
+        if *file.version.get_ref() != SCENE_VERSION {
+            return Err(error_at(Some(file.version.span()),
+                format!("unsupported scene version {} (expected {SCENE_VERSION})", file.version.get_ref())));
+        }
+
+        // An empty or degenerate image can't be rendered at all
+        let camera = &file.camera;
+        if let Some(ratio) = &camera.aspect_ratio
+            && !(ratio.get_ref().is_finite() && *ratio.get_ref() > 0.0) {
+            return Err(error_at(Some(ratio.span()), format!("aspect_ratio must be positive, got {}", ratio.get_ref())));
+        }
+        for (field, value) in [("image_width", &camera.image_width), ("samples_per_pixel", &camera.samples_per_pixel)] {
+            if let Some(value) = value
+                && *value.get_ref() == 0 {
+                return Err(error_at(Some(value.span()), format!("{field} must be at least 1")));
+            }
+        }
+
+        // Textures are built once and shared, and image files are read here so
+        // a bad file fails the load instead of the render
+        let mut textures: HashMap<&str, Arc<dyn Texture>> = HashMap::new();
+        for (name, desc) in &file.textures {
+            let texture = build_texture(desc.get_ref(), dir).map_err(|message| error_at(Some(desc.span()), message))?;
+            textures.insert(name, texture);
+        }
+        for desc in file.materials.values() {
+            if let MaterialDesc::Lambertian { albedo: AlbedoDesc::Texture(name), .. }
+                | MaterialDesc::Metal { albedo: AlbedoDesc::Texture(name), .. }
+                | MaterialDesc::Microfacet { base_color: AlbedoDesc::Texture(name), .. } = desc.get_ref()
+                && !textures.contains_key(name.as_str()) {
+                return Err(error_at(Some(desc.span()), format!("unknown texture `{name}`")));
+            }
+            if let MaterialDesc::Dialectric { refraction_index, dispersion, .. } = desc.get_ref()
+                && refraction_index.is_some() == dispersion.is_some() {
+                let message = "dialectric needs either `refraction_index` or `dispersion`";
+                return Err(error_at(Some(desc.span()), message.to_string()));
+            }
+        }
+
+        // Every material is built once and shared by all the objects naming it
+        let mut library = MaterialLibrary::new();
+        for (name, desc) in &file.materials {
+            library.insert(name, build_material(desc.get_ref(), &textures));
+        }
+        let material = |name: &String| -> Result<Arc<dyn Material>, String> {
+            library.get(name).ok_or_else(|| format!("unknown material `{name}`"))
+        };
+
+        // Shared geometry is built once, however many instances use it
+        let mut geometry: HashMap<&str, Part> = HashMap::new();
+        for (name, desc) in &file.geometry {
+            let at = |message: String| error_at(Some(desc.span()), message);
+            let mut parts = build_shape(desc.get_ref(), material, dir).map_err(at)?;
+            let emissive = parts.iter().any(|(emissive, _)| *emissive);
+            let mut shape = match parts.len() {
+                1 => parts.remove(0).1,
+                _ => {
+                    let mut list = HittableList::new();
+                    parts.into_iter().for_each(|(_, part)| list.add(part));
+                    Arc::new(BvhNode::new(list))
+                }
+            };
+            if let Some(matrix) = build_transform(desc.get_ref().transform()).map_err(at)? {
+                shape = Arc::new(Transform::new(shape, matrix));
+            }
+            geometry.insert(name, (emissive, shape));
+        }
+
+        let mut scene = Scene::new(build_camera(&file.camera));
+        for object in &file.objects {
+            let at = |message: String| error_at(Some(object.span()), message);
+            let matrix = build_transform(object.get_ref().transform()).map_err(at)?;
+            if let ObjectDesc::Instance { geometry: name, material: mat, .. } = object.get_ref() {
+                let (emissive, shape) = geometry.get(name.as_str()).ok_or_else(|| at(format!("unknown geometry `{name}`")))?;
+                let shape = Arc::clone(shape);
+                let matrix = matrix.unwrap_or(DMat4::IDENTITY);
+                match mat {
+                    Some(name) => {
+                        let mat = material(name).map_err(at)?;
+                        scene.place(mat.is_emissive(), None, Instance::with_material(shape, matrix, mat));
+                    }
+                    None => scene.place(*emissive, None, Instance::new(shape, matrix)),
+                }
+                continue;
+            }
+            for (emissive, part) in build_shape(object.get_ref(), material, dir).map_err(at)? {
+                scene.place(emissive, matrix, part);
+            }
+        }
+
+        scene.lights.extend(file.lights.iter()
+            .map(|l| Light::new(vec3(l.position), color(l.color), l.intensity)));
+        scene.materials = library;
+
+        Ok(scene)
+    }
+
+    fn place(&mut self, emissive: bool, matrix: Option<DMat4>, object: impl Hittable + 'static) {
+        let object: Arc<dyn Hittable> = match matrix {
+            Some(matrix) => Arc::new(Transform::new(object, matrix)),
+            None => Arc::new(object),
+        };
+        if emissive {
+            self.add_light(object);
+        } else {
+            self.add(object);
+        }
+    }
+}
+
+
+impl Scene {
+    // Human readable overview of what would be rendered
+    pub fn summary(&self) -> String {
+        let cam = &self.camera;
+        let v = |p: Vec3| format!("({}, {}, {})", p.x, p.y, p.z);
+        let mut out = String::new();
+        out += &format!("Image:   {}x{}, {} samples per pixel, max depth {}, {} integrator{}\n",
+            cam.image_width, cam.image_height(), cam.samples_per_pixel, cam.max_depth, cam.integrator.name(),
+            if cam.spectral { ", spectral" } else { "" });
+        out += &format!("Camera:  from {} looking at {}, vfov {}, focus distance {}, defocus angle {}\n",
+            v(cam.lookfrom), v(cam.lookat), cam.vfov, cam.focus_dist, cam.defocus_angle);
+        out += &format!("Objects: {}\n", self.world.len());
+        if self.world.len() > 0 {
+            out += &match self.world.bounding_box() {
+                Some(b) => format!("Bounds:  x [{}, {}], y [{}, {}], z [{}, {}]\n",
+                    b.x.min, b.x.max, b.y.min, b.y.max, b.z.min, b.z.max),
+                None => "Bounds:  unbounded (the scene has an infinite plane)\n".to_string(),
+            };
+        }
+        let mut names: Vec<&str> = self.materials.names().collect();
+        if !names.is_empty() {
+            names.sort();
+            out += &format!("Materials: {}\n", names.join(", "));
+        }
+        out += &format!("Lights:  {}\n", self.lights.len());
+        for light in &self.lights {
+            out += &match light {
+                Light::Point { position, intensity, .. } => format!("         at {}, intensity {}\n", v(*position), intensity),
+                Light::Area(shape) => match shape.bounding_box() {
+                    Some(b) => format!("         area light around {}\n", v(b.centroid())),
+                    None => "         unbounded emitter, not sampled\n".to_string(),
+                },
+            };
+        }
+        out
+    }
+}
+
+// A built object and whether it glows
+type Part = (bool, Arc<dyn Hittable>);
+
+// The object as one or more parts. Only OBJ files
+// give more than one part, one per mesh.
+fn build_shape<F>(desc: &ObjectDesc, material: F, dir: &Path) -> Result<Vec<Part>, String>
+where
+    F: Fn(&String) -> Result<Arc<dyn Material>, String>,
+{
+    let part: Part = match desc {
+        ObjectDesc::Sphere { center, radius, material: name, .. } => {
+            if *radius <= 0.0 {
+                return Err(format!("sphere radius must be positive, got {radius}"));
+            }
+            let mat = material(name)?;
+            (mat.is_emissive(), Arc::new(Sphere::new(vec3(*center), *radius, mat)))
+        }
+        ObjectDesc::Cube { center, size, min, max, material: name, .. } => {
+            let mat = material(name)?;
+            let emissive = mat.is_emissive();
+            let cube = match (center, size, min, max) {
+                (Some(center), Some(size), None, None) => Cube::from_center_size(vec3(*center), *size, mat),
+                (None, None, Some(min), Some(max)) => Cube::new(vec3(*min), vec3(*max), mat),
+                _ => return Err("cube needs either `center` and `size`, or `min` and `max`".to_string()),
+            };
+            (emissive, Arc::new(cube))
+        }
+        ObjectDesc::Cylinder { base, top, center, height, radius, capped, material: name, .. } => {
+            if *radius <= 0.0 {
+                return Err(format!("cylinder radius must be positive, got {radius}"));
+            }
+            let mat = material(name)?;
+            let emissive = mat.is_emissive();
+            let (base, top) = match (base, top, center, height) {
+                (Some(base), Some(top), None, None) => (vec3(*base), vec3(*top)),
+                (None, None, Some(center), Some(height)) => {
+                    let half = Vec3::new(0.0, height / 2.0, 0.0);
+                    (vec3(*center) - half, vec3(*center) + half)
+                }
+                _ => return Err("cylinder needs either `base` and `top`, or `center` and `height`".to_string()),
+            };
+            if base == top {
+                return Err("cylinder `base` and `top` are the same point".to_string());
+            }
+            (emissive, Arc::new(Cylinder::new(base, top, *radius, *capped, mat)))
+        }
+        ObjectDesc::Plane { point, normal, material: name, .. } => {
+            if vec3(*normal).length_squared() == 0.0 {
+                return Err("plane `normal` can't be zero".to_string());
+            }
+            let mat = material(name)?;
+            (mat.is_emissive(), Arc::new(Plane::new(vec3(*point), vec3(*normal), mat)))
+        }
+        ObjectDesc::Rect { corner, u, v, material: name, .. } => {
+            if Vec3::cross(vec3(*u), vec3(*v)).length_squared() == 0.0 {
+                return Err("rect edges `u` and `v` are zero or parallel".to_string());
+            }
+            let mat = material(name)?;
+            (mat.is_emissive(), Arc::new(Rect::new(vec3(*corner), vec3(*u), vec3(*v), mat)))
+        }
+        ObjectDesc::Disk { center, normal, radius, material: name, .. } => {
+            if *radius <= 0.0 {
+                return Err(format!("disk radius must be positive, got {radius}"));
+            }
+            if vec3(*normal).length_squared() == 0.0 {
+                return Err("disk `normal` can't be zero".to_string());
+            }
+            let mat = material(name)?;
+            (mat.is_emissive(), Arc::new(Disk::new(vec3(*center), vec3(*normal), *radius, mat)))
+        }
+        ObjectDesc::Triangle { vertices: [a, b, c], material: name, .. } => {
+            if Vec3::cross(vec3(*b) - vec3(*a), vec3(*c) - vec3(*a)).length_squared() == 0.0 {
+                return Err("triangle `vertices` are in a line".to_string());
+            }
+            let mat = material(name)?;
+            (mat.is_emissive(), Arc::new(Triangle::new(vec3(*a), vec3(*b), vec3(*c), mat)))
+        }
+        ObjectDesc::Mesh { positions, normals, uvs, indices, material: name, .. } => {
+            let data = MeshData {
+                positions: positions.iter().copied().map(vec3).collect(),
+                normals: normals.iter().copied().map(vec3).collect(),
+                uvs: uvs.iter().map(|uv| (uv[0], uv[1])).collect(),
+                indices: indices.clone(),
+            };
+            if let Some(message) = mesh_error(&data) {
+                return Err(message);
+            }
+            let mat = material(name)?;
+            (mat.is_emissive(), Arc::new(TriangleMesh::new(data, mat)))
+        }
+        ObjectDesc::Obj { path, material: name, .. } => {
+            let model = obj::load(&dir.join(path)).map_err(|err| err.to_string())?;
+            // Built once per model, like the scene's own materials
+            let mtl: HashMap<&String, Arc<dyn Material>> = model.materials.iter()
+                .map(|(name, m)| (name, m.to_material()))
+                .collect();
+            let fallback: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8), 0.0));
+            let mut parts = Vec::new();
+            for mesh in model.meshes {
+                let mat = match (name, &mesh.material) {
+                    (Some(name), _) => material(name)?,
+                    (None, Some(name)) => Arc::clone(&mtl[name]),
+                    (None, None) => Arc::clone(&fallback),
+                };
+                parts.push((mat.is_emissive(), Arc::new(TriangleMesh::new(mesh.data, mat)) as _));
+            }
+            return Ok(parts);
+        }
+        ObjectDesc::Instance { .. } => return Err("an instance can't be used as geometry".to_string()),
+    };
+    Ok(vec![part])
+}
+
+// None when there's nothing to do, so untransformed objects aren't wrapped
+fn build_transform(steps: &[TransformDesc]) -> Result<Option<DMat4>, String> {
+    if steps.is_empty() {
+        return Ok(None);
+    }
+    let mut matrix = DMat4::IDENTITY;
+    for step in steps {
+        let m = match step {
+            TransformDesc::Translate(offset) => transform::translate(vec3(*offset)),
+            TransformDesc::Rotate { axis, degrees } => {
+                if vec3(*axis).near_zero() {
+                    return Err("rotation `axis` can't be zero".to_string());
+                }
+                transform::rotate(vec3(*axis), *degrees)
+            }
+            TransformDesc::Scale(ScaleDesc::Uniform(s)) => transform::scale(Vec3::new(*s, *s, *s)),
+            TransformDesc::Scale(ScaleDesc::Axes(s)) => transform::scale(vec3(*s)),
+            TransformDesc::LookAt { from, to, up } => {
+                let forward = vec3(*to) - vec3(*from);
+                if forward.near_zero() || Vec3::cross(vec3(*up), forward).near_zero() {
+                    return Err("look_at needs `from` and `to` apart, and `up` not along the line between them".to_string());
+                }
+                transform::look_at(vec3(*from), vec3(*to), vec3(*up))
+            }
+        };
+        // Later steps act on the result of earlier ones
+        matrix = m * matrix;
+    }
+    if matrix.determinant().abs() < 1e-12 {
+        return Err("transform flattens the object (a scale of zero?)".to_string());
+    }
+    Ok(Some(matrix))
+}
+
+fn build_texture(desc: &TextureDesc, dir: &Path) -> Result<Arc<dyn Texture>, String> {
+    let solid = |c: [f64; 3]| -> Arc<dyn Texture> { Arc::new(SolidColor::new(color(c))) };
+    Ok(match *desc {
+        TextureDesc::Solid { color } => solid(color),
+        TextureDesc::Checker { scale, even, odd } => {
+            if scale == 0.0 {
+                return Err("checker `scale` can't be zero".to_string());
+            }
+            Arc::new(CheckerTexture::new(scale, solid(even), solid(odd)))
+        }
+        TextureDesc::UvChecker { columns, rows, even, odd } => {
+            Arc::new(UvCheckerTexture::new(columns, rows, solid(even), solid(odd)))
+        }
+        TextureDesc::Image { ref path, wrap, filter } => {
+            Arc::new(ImageTexture::load(&dir.join(path), wrap, filter).map_err(|err| err.to_string())?)
+        }
+        TextureDesc::Noise { kind, scale, octaves, low, high, seed } => {
+            Arc::new(NoiseTexture::new(Perlin::new(seed), kind, scale, octaves, color(low), color(high)))
+        }
+    })
+}
+
+// Texture names have already been checked
+fn build_material(desc: &MaterialDesc, textures: &HashMap<&str, Arc<dyn Texture>>) -> Arc<dyn Material> {
+    let texture = |albedo: &AlbedoDesc| -> Arc<dyn Texture> {
+        match albedo {
+            AlbedoDesc::Color(c) => Arc::new(SolidColor::new(color(*c))),
+            AlbedoDesc::Texture(name) => Arc::clone(&textures[name.as_str()]),
+        }
+    };
+    match *desc {
+        MaterialDesc::Lambertian { ref albedo, brightness } => Arc::new(Lambertian::from_texture(texture(albedo), brightness)),
+        MaterialDesc::Metal { ref albedo, fuzz, brightness } => Arc::new(Metal::from_texture(texture(albedo), fuzz, brightness)),
+        MaterialDesc::Dialectric { refraction_index, ref dispersion, tint, absorption, brightness } => {
+            let (tint, absorption) = (color(tint), color(absorption));
+            match *dispersion {
+                Some(DispersionDesc::Cauchy { a, b }) => {
+                    Arc::new(Dialectric::dispersive(Dispersion::Cauchy { a, b }, tint, absorption, brightness))
+                }
+                Some(DispersionDesc::Sellmeier { b, c }) => {
+                    Arc::new(Dialectric::dispersive(Dispersion::Sellmeier { b, c }, tint, absorption, brightness))
+                }
+                // Checked when the file was loaded
+                None => Arc::new(Dialectric::colored(refraction_index.unwrap_or(1.5), tint, absorption, brightness)),
+            }
+        }
+        MaterialDesc::Microfacet { ref base_color, roughness, metallic, brightness } => {
+            Arc::new(Microfacet::from_texture(texture(base_color), roughness, metallic, brightness))
+        }
+        MaterialDesc::Light { color: c, intensity } => Arc::new(DiffuseLight::new(color(c), intensity)),
+    }
+}
+
+// TriangleMesh::new asserts these, so catch them here with a proper error
+fn mesh_error(data: &MeshData) -> Option<String> {
+    let n = data.positions.len();
+    if !data.normals.is_empty() && data.normals.len() != n {
+        return Some(format!("mesh has {} normals for {n} positions", data.normals.len()));
+    }
+    if !data.uvs.is_empty() && data.uvs.len() != n {
+        return Some(format!("mesh has {} uvs for {n} positions", data.uvs.len()));
+    }
+    data.indices.iter().flatten().find(|&&i| i >= n)
+        .map(|i| format!("mesh index {i} is out of range for {n} positions"))
+}
+
+fn build_camera(desc: &CameraDesc) -> Camera {
+    let mut cam = Camera::new();
+    // Unset fields keep Camera::new()'s defaults
+    if let Some(v) = &desc.aspect_ratio { cam.aspect_ratio = *v.get_ref(); }
+    if let Some(v) = &desc.image_width { cam.image_width = *v.get_ref(); }
+    if let Some(v) = &desc.samples_per_pixel { cam.samples_per_pixel = *v.get_ref(); }
+    if let Some(v) = desc.max_depth { cam.max_depth = v; }
+    if let Some(v) = desc.vfov { cam.vfov = v; }
+    if let Some(v) = desc.lookfrom { cam.lookfrom = vec3(v); }
+    if let Some(v) = desc.lookat { cam.lookat = vec3(v); }
+    if let Some(v) = desc.vup { cam.vup = vec3(v); }
+    if let Some(v) = desc.defocus_angle { cam.defocus_angle = v; }
+    if let Some(v) = desc.focus_dist { cam.focus_dist = v; }
+    if let Some(v) = desc.spectral { cam.spectral = v; }
+    if let Some(v) = desc.integrator { cam.integrator = v; }
+    cam
+}
+
+// 1-based line and column of a byte offset
+fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset.min(src.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::Point3;
+
+    const EXAMPLE: &str = r#"
+version = 1
+
+[camera]
+image_width = 200
+lookfrom = [8.0, 3.0, 5.0]
+
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.2, 0.2]
+brightness = 0.5
+
+[[objects]]
+type = "sphere"
+center = [0.0, 1.0, 0.0]
+radius = 0.8
+material = "red"
+
+[[objects]]
+type = "plane"
+point = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+material = "red"
+
+[[lights]]
+position = [5.0, 6.0, 3.0]
+"#;
+
+    fn parse_error(src: &str) -> (usize, usize, String) {
+        match Scene::parse(src, Path::new("")) {
+            Err(SceneError::Parse { line, column, message }) => (line, column, message),
+            Err(err) => panic!("unexpected error {err}"),
+            Ok(_) => panic!("scene should not parse"),
+        }
+    }
+
+    #[test]
+    fn test_parse_example() {
+        let scene = Scene::parse(EXAMPLE, Path::new("")).unwrap();
+        assert_eq!(scene.camera.image_width, 200);
+        assert_eq!(scene.camera.samples_per_pixel, Camera::new().samples_per_pixel);
+        assert_eq!(scene.camera.integrator, IntegratorKind::Path);
+        let ao = Scene::parse(&EXAMPLE.replace("image_width = 200", "image_width = 200\nintegrator = \"ao\""), Path::new("")).unwrap();
+        assert_eq!(ao.camera.integrator, IntegratorKind::Ao);
+        assert_eq!(scene.world.len(), 2);
+        assert!(scene.world.bounding_box().is_none());
+        // The red material glows, so both objects are lights; the sphere is the only one that can be sampled
+        assert_eq!(scene.lights.len(), 3);
+        assert!(matches!(scene.lights[2], Light::Point { intensity: 1.0, .. }));
+        let Light::Area(sphere) = &scene.lights[0] else { panic!("sphere should be an area light") };
+        assert!(sphere.sample(Point3::new(0.0, 5.0, 0.0)).is_some());
+        // Built once: held by the library, both objects and this handle
+        let red = scene.materials.get("red").unwrap();
+        assert_eq!(Arc::strong_count(&red), 4);
+    }
+
+    #[test]
+    fn test_object_transforms() {
+        let src = EXAMPLE.replace("radius = 0.8\n", "radius = 0.8\ntransform = [{ scale = [2.0, 1.0, 1.0] }, { translate = [1.0, 0.0, 0.0] }]\n");
+        let scene = Scene::parse(&src, Path::new("")).unwrap();
+        let Light::Area(sphere) = &scene.lights[0] else { panic!("sphere should be an area light") };
+        let b = sphere.bounding_box().unwrap();
+        assert!((b.x.min + 0.6).abs() < 1e-9 && (b.x.max - 2.6).abs() < 1e-9);
+        assert!((b.y.max - 1.8).abs() < 1e-9);
+        // Stretched, the sphere can no longer be sampled as a light
+        assert!(sphere.sample(Point3::new(0.0, 5.0, 0.0)).is_none());
+
+        let (line, _, message) = parse_error(&EXAMPLE.replace("radius = 0.8\n", "radius = 0.8\ntransform = [{ scale = 0.0 }]\n"));
+        assert_eq!(line, 13);
+        assert!(message.contains("scale"));
+        let (line, _, message) = parse_error(&EXAMPLE.replace("radius = 0.8\n", "radius = 0.8\ntransform = [{ spin = 1.0 }]\n"));
+        assert_eq!(line, 13);
+        assert!(message.contains("spin"));
+    }
+
+    #[test]
+    fn test_instances() {
+        let src = r#"
+version = 1
+
+[materials.grey]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[materials.glow]
+type = "light"
+
+[geometry.pebble]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 0.5
+material = "grey"
+
+[[objects]]
+type = "instance"
+geometry = "pebble"
+transform = [{ translate = [2.0, 0.0, 0.0] }]
+
+[[objects]]
+type = "instance"
+geometry = "pebble"
+material = "glow"
+transform = [{ scale = 2.0 }, { translate = [-2.0, 0.0, 0.0] }]
+"#;
+        let scene = Scene::parse(src, Path::new("")).unwrap();
+        assert_eq!(scene.world.len(), 2);
+        let b = scene.world.bounding_box().unwrap();
+        assert!((b.x.min + 3.0).abs() < 1e-9 && (b.x.max - 2.5).abs() < 1e-9);
+        // Only the instance with the glowing material is a light
+        assert_eq!(scene.lights.len(), 1);
+        let Light::Area(glowing) = &scene.lights[0] else { panic!("instance should be an area light") };
+        assert!(glowing.sample(Point3::new(0.0, 5.0, 0.0)).is_some());
+
+        let (line, _, message) = parse_error(&src.replace("geometry = \"pebble\"\nmaterial", "geometry = \"rock\"\nmaterial"));
+        assert_eq!(line, 22);
+        assert!(message.contains("rock"));
+    }
+
+    #[test]
+    fn test_errors_report_position() {
+        let (line, column, _) = parse_error("version = 1\n[camera]\nvfov = \"wide\"\n");
+        assert_eq!((line, column), (3, 8));
+
+        let (line, _, message) = parse_error(&EXAMPLE.replace("material = \"red\"\n\n[[lights]]", "material = \"blue\"\n\n[[lights]]"));
+        assert_eq!(line, 19);
+        assert!(message.contains("blue"));
+
+        let (line, column, message) = parse_error("\nversion = 7\n");
+        assert_eq!((line, column), (2, 11));
+        assert!(message.contains("version"));
+
+        let (line, _, _) = parse_error("version = 1\n[[objects]]\ntype = \"torus\"\n");
+        assert_eq!(line, 3);
+
+        let (line, _, message) = parse_error(&EXAMPLE.replace("albedo = [0.8, 0.2, 0.2]", "albedo = \"marble\""));
+        assert_eq!(line, 8);  // the [materials.red] header
+        assert!(message.contains("marble"));
+
+        let (line, _, message) = parse_error("version = 1\n\n[textures.wood]\ntype = \"image\"\npath = \"missing.png\"\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("missing.png"));
+        parse_error("version = 1\n[camera\n");
+        parse_error("");
+    }
+
+    #[test]
+    fn test_camera_values_are_checked() {
+        for ratio in ["0.0", "-1.5", "nan", "inf"] {
+            let (line, column, message) = parse_error(&format!("version = 1\n[camera]\naspect_ratio = {ratio}\n"));
+            assert_eq!((line, column), (3, 16));
+            assert!(message.contains("aspect_ratio"), "{message}");
+        }
+        let (line, column, message) = parse_error(&EXAMPLE.replace("image_width = 200", "image_width = 0"));
+        assert_eq!((line, column), (5, 15));
+        assert!(message.contains("image_width"));
+        let (line, column, message) = parse_error("version = 1\n[camera]\nsamples_per_pixel = 0\n");
+        assert_eq!((line, column), (3, 21));
+        assert!(message.contains("samples_per_pixel"));
+    }
+
+    #[test]
+    fn test_degenerate_shapes_are_rejected() {
+        let (line, _, message) = parse_error(&EXAMPLE.replace("radius = 0.8", "radius = 0.0"));
+        assert_eq!(line, 13);
+        assert!(message.contains("radius"), "{message}");
+        let (line, _, message) = parse_error(&EXAMPLE.replace("normal = [0.0, 1.0, 0.0]", "normal = [0.0, 0.0, 0.0]"));
+        assert_eq!(line, 19);
+        assert!(message.contains("normal"), "{message}");
+        // Each shape on its own, as the only object after a 5 line preamble
+        let shapes = [
+            ("type = \"cylinder\"\ncenter = [0.0, 0.0, 0.0]\nradius = -1.0\nheight = 1.0", "radius"),
+            ("type = \"triangle\"\nvertices = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [2.0, 2.0, 2.0]]", "vertices"),
+            ("type = \"rect\"\ncorner = [0.0, 0.0, 0.0]\nu = [1.0, 0.0, 0.0]\nv = [-2.0, 0.0, 0.0]", "edges"),
+            ("type = \"disk\"\ncenter = [0.0, 0.0, 0.0]\nnormal = [0.0, 1.0, 0.0]\nradius = 0.0", "radius"),
+            ("type = \"disk\"\ncenter = [0.0, 0.0, 0.0]\nnormal = [0.0, 0.0, 0.0]\nradius = 1.0", "normal"),
+        ];
+        for (shape, field) in shapes {
+            let src = format!("version = 1\n[materials.m]\ntype = \"dialectric\"\nrefraction_index = 1.5\n\n\
+                [[objects]]\n{shape}\nmaterial = \"m\"\n");
+            let (line, column, message) = parse_error(&src);
+            assert_eq!((line, column), (6, 1), "{shape}");
+            assert!(message.contains(field), "{message}");
+        }
+
+        let checker = "version = 1\n\n[textures.floor]\ntype = \"checker\"\nscale = 0.0\neven = [1.0, 1.0, 1.0]\nodd = [0.0, 0.0, 0.0]\n";
+        let (line, column, message) = parse_error(checker);
+        assert_eq!((line, column), (3, 1));
+        assert!(message.contains("scale"), "{message}");
+    }
+
+    #[test]
+    fn test_dispersive_glass() {
+        let prism = "version = 1\n\n[materials.flint]\ntype = \"dialectric\"\n";
+        let scene = Scene::parse(&format!("{prism}dispersion = {{ model = \"cauchy\", a = 1.67, b = 0.0074 }}\n"), Path::new("")).unwrap();
+        assert!(scene.materials.get("flint").is_some());
+        Scene::parse(&format!("{prism}dispersion = {{ model = \"sellmeier\", b = [1.0, 0.2, 1.0], c = [0.006, 0.02, 103.6] }}\n"), Path::new("")).unwrap();
+
+        // Exactly one of the two ways to give the index
+        let (line, _, message) = parse_error(prism);
+        assert_eq!(line, 3);
+        assert!(message.contains("dispersion"));
+        let (line, _, _) = parse_error(&format!("{prism}refraction_index = 1.5\ndispersion = {{ model = \"cauchy\", a = 1.5, b = 0.004 }}\n"));
+        assert_eq!(line, 3);
+        parse_error(&format!("{prism}dispersion = {{ model = \"abbe\", a = 1.5 }}\n"));
+    }
+}
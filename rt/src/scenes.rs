@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use crate::vec3::{Vec3, Point3};
+use crate::sphere::Sphere;
+use crate::cylinder::Cylinder;
+use crate::cube::Cube;
+use crate::plane::Plane;
+use crate::camera::Camera;
+use crate::material::{Lambertian, Metal};
+use crate::color::Color;
+use crate::light::Light;
+use crate::scene::Scene;
+
+type SceneBuilder = fn() -> Scene;
+
+// Built-in scenes, selectable by name from the command line
+pub const BUILTIN_SCENES: [(&str, SceneBuilder); 5] = [
+    ("sphere", sphere),
+    ("plane-cube", plane_cube),
+    ("all-objects", all_objects),
+    ("all-objects-front", all_objects_front),
+    ("cylinders", cylinders),
+];
+
+pub fn builtin(name: &str) -> Option<Scene> {
+    BUILTIN_SCENES.iter().find(|(n, _)| *n == name).map(|(_, build)| build())
+}
+
+fn ground(albedo: f64) -> Plane {
+    Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(albedo, albedo, albedo), 0.0))
+    )
+}
+
+fn camera(lookfrom: Point3, vfov: f64, focus_dist: f64) -> Camera {
+    let mut cam = Camera::new();
+    cam.aspect_ratio = 16.0/9.0;
+    cam.image_width = 600;
+    cam.samples_per_pixel = 500;
+    cam.max_depth = 50;
+    cam.vfov = vfov;
+    cam.lookfrom = lookfrom;
+    cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.focus_dist = focus_dist;
+    cam
+}
+
+// ============================================================================
+// SCENE 1: SPHERE SCENE
+// ============================================================================
+// Requirements: Scene with a sphere, clear shadows visible
+fn sphere() -> Scene {
+    let mut scene = Scene::new(camera(Point3::new(8.0, 3.0, 5.0), 20.0, 10.0));
+
+    // Ground plane - Dark surface to show shadows clearly
+    scene.add(ground(0.2));
+
+    // Bright sphere - High brightness to cast visible shadows
+    let sphere_mat = Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3), 0.6));
+    scene.add_light(Sphere::new(Point3::new(0.0, 1.5, 0.0), 1.0, sphere_mat));
+
+    // Light source positioned to create clear shadows
+    scene.lights.push(Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.0));
+    scene
+}
+
+// ============================================================================
+// SCENE 2: PLANE AND CUBE (LOWER BRIGHTNESS)
+// ============================================================================
+// Requirements: Flat plane and cube with lower brightness than Scene 1 sphere
+fn plane_cube() -> Scene {
+    let mut scene = Scene::new(camera(Point3::new(7.0, 3.0, 4.0), 20.0, 10.0));
+
+    // Ground plane - Flat surface
+    scene.add(ground(0.25));
+
+    // Cube with lower brightness than Scene 1 sphere (0.3 < 0.6)
+    let cube_mat = Arc::new(Lambertian::new(Color::new(0.3, 0.5, 0.8), 0.3));
+    scene.add_light(Cube::from_center_size(
+        Point3::new(0.0, 1.0, 0.0),
+        1.5,
+        cube_mat
+    ));
+
+    // Light source
+    scene.lights.push(Light::new(Point3::new(4.0, 5.0, 2.0), Color::new(1.0, 1.0, 1.0), 1.0));
+    scene
+}
+
+// ============================================================================
+// SCENE 3: ALL OBJECTS (ONE OF EACH)
+// ============================================================================
+// Requirements: One cube, one sphere, one cylinder, one flat plane
+// Clear shadows visible from all objects
+fn all_objects() -> Scene {
+    let mut scene = Scene::new(camera(Point3::new(8.0, 3.0, 5.0), 20.0, 10.0));
+
+    // Flat plane (ground)
+    scene.add(ground(0.3));
+
+    // One sphere - positioned on the left
+    let sphere_mat = Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.2), 0.5));
+    scene.add_light(Sphere::new(Point3::new(-2.5, 1.0, 0.0), 0.8, sphere_mat));
+
+    // One cube - positioned in the center
+    let cube_mat = Arc::new(Lambertian::new(Color::new(0.2, 0.6, 0.8), 0.4));
+    scene.add_light(Cube::from_center_size(
+        Point3::new(0.0, 1.0, 0.0),
+        1.2,
+        cube_mat
+    ));
+
+    // One cylinder - positioned on the right
+    let cylinder_mat = Arc::new(Lambertian::new(Color::new(0.6, 0.8, 0.2), 0.45));
+    scene.add_light(Cylinder::from_center_height(Point3::new(2.5, 1.0, 0.0), 0.6, 2.0, cylinder_mat));
+
+    // Light sources positioned to create clear shadows
+    scene.lights.extend([
+        Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.2),
+        Light::new(Point3::new(-4.0, 4.0, 2.0), Color::new(1.0, 0.95, 0.9), 0.8),
+    ]);
+    scene
+}
+
+// ============================================================================
+// SCENE 4: ALL OBJECTS FROM DIFFERENT PERSPECTIVE
+// ============================================================================
+// Requirements: Same objects as Scene 3, but camera in different position
+fn all_objects_front() -> Scene {
+    // Viewing from the front, moved back and up with a wider field of view to see all objects
+    let mut scene = Scene::new(camera(Point3::new(0.0, 3.5, 10.0), 35.0, 12.0));
+
+    // Flat plane (ground) - same as Scene 3
+    scene.add(ground(0.3));
+
+    // One sphere - Mirror (reflective)
+    let sphere_mat = Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.0, 0.0));  // Perfect mirror
+    scene.add(Sphere::new(Point3::new(-2.5, 1.0, 0.0), 0.8, sphere_mat));
+
+    // One cube - same position as Scene 3
+    let cube_mat = Arc::new(Lambertian::new(Color::new(0.2, 0.6, 0.8), 0.4));
+    scene.add_light(Cube::from_center_size(
+        Point3::new(0.0, 1.0, 0.0),
+        1.2,
+        cube_mat
+    ));
+
+    // One cylinder - same position as Scene 3
+    let cylinder_mat = Arc::new(Lambertian::new(Color::new(0.6, 0.8, 0.2), 0.45));
+    scene.add_light(Cylinder::from_center_height(Point3::new(2.5, 1.0, 0.0), 0.6, 2.0, cylinder_mat));
+
+    // Only the first of Scene 3's light sources
+    scene.lights.push(Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.2));
+    scene
+}
+
+// ============================================================================
+// SCENE 5: TWO CYLINDERS AT DIFFERENT ANGLES
+// ============================================================================
+// Two cylinders with different colors, positioned at different angles
+fn cylinders() -> Scene {
+    let mut scene = Scene::new(camera(Point3::new(0.0, 3.0, 8.0), 30.0, 10.0));
+
+    // Ground plane
+    scene.add(ground(0.3));
+
+    // First cylinder - Red/Orange, on the left, taller and leaning right and back
+    let cylinder1_mat = Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.2), 0.3));
+    scene.add_light(Cylinder::new(
+        Point3::new(-2.6, 0.0, 0.3),
+        Point3::new(-1.5, 2.8, -0.5),
+        0.5,
+        true,
+        cylinder1_mat
+    ));
+
+    // Second cylinder - Blue/Purple, on the right, shorter and wider, lying on its side
+    let cylinder2_mat = Arc::new(Lambertian::new(Color::new(0.2, 0.4, 0.9), 0.35));
+    scene.add_light(Cylinder::new(
+        Point3::new(1.1, 0.7, 0.8),
+        Point3::new(2.9, 0.7, -0.5),
+        0.7,
+        true,
+        cylinder2_mat
+    ));
+
+    // Light source positioned to show shadows clearly
+    scene.lights.push(Light::new(Point3::new(5.0, 6.0, 3.0), Color::new(1.0, 1.0, 1.0), 1.2));
+    scene
+}
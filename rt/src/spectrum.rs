@@ -0,0 +1,142 @@
+use std::sync::OnceLock;
+use crate::color::Color;
+
+// Visible range sampled by the camera, in nanometres
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 720.0;
+
+// Spreads `u` in [0, 1) uniformly over the visible range
+pub fn sample_wavelength(u: f64) -> f64 {
+    MIN_WAVELENGTH + u * (MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+// Piecewise Gaussian used by the colour matching function fit
+fn lobe(wavelength: f64, mean: f64, below: f64, above: f64) -> f64 {
+    let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 standard observer colour matching functions, from the multi-lobe
+// fit of Wyman, Sloan and Shirley (2013)
+pub fn cie_xyz(wavelength: f64) -> [f64; 3] {
+    let l = wavelength;
+    let x = 1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+    [x, y, z]
+}
+
+// CIE XYZ to linear sRGB
+fn xyz_to_rgb([x, y, z]: [f64; 3]) -> Color {
+    Color::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+// What a sample of light at one wavelength adds to the pixel, for
+// wavelengths drawn by `sample_wavelength`. Scaled per channel so that light
+// spread evenly over the spectrum comes out white; single wavelengths can
+// have a slightly negative channel, as real spectral colours lie outside
+// the sRGB gamut.
+pub fn film_weight(wavelength: f64) -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white = WHITE.get_or_init(|| {
+        let steps = 1000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for i in 0..steps {
+            let l = MIN_WAVELENGTH + (i as f64 + 0.5) / steps as f64 * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+            sum += (1.0 / steps as f64) * xyz_to_rgb(cie_xyz(l));
+        }
+        sum
+    });
+    let rgb = xyz_to_rgb(cie_xyz(wavelength));
+    Color::new(rgb.r / white.r, rgb.g / white.g, rgb.b / white.b)
+}
+
+// Smits (1999) basis spectra for turning RGB into a smooth spectrum, ten
+// bins evenly spaced over the visible range
+const WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Value of one basis spectrum, interpolated between bin centres
+fn basis(spectrum: &[f64; 10], wavelength: f64) -> f64 {
+    let x = (wavelength - MIN_WAVELENGTH) / (MAX_WAVELENGTH - MIN_WAVELENGTH) * 10.0 - 0.5;
+    let i = (x.floor().max(0.0) as usize).min(8);
+    let t = (x - i as f64).clamp(0.0, 1.0);
+    (1.0 - t) * spectrum[i] + t * spectrum[i + 1]
+}
+
+// Value at `wavelength` of a smooth spectrum that looks like `c`. Used for
+// reflectances and emitters alike: the grey part comes from the flat white
+// spectrum, the rest from the two primaries or secondaries nearest the hue.
+pub fn rgb_to_spectrum(c: Color, wavelength: f64) -> f64 {
+    let at = |spectrum: &[f64; 10]| basis(spectrum, wavelength);
+    let (r, g, b) = (c.r, c.g, c.b);
+    if r <= g && r <= b {
+        r * at(&WHITE) + if g <= b { (g - r) * at(&CYAN) + (b - g) * at(&BLUE) } else { (b - r) * at(&CYAN) + (g - b) * at(&GREEN) }
+    } else if g <= r && g <= b {
+        g * at(&WHITE) + if r <= b { (r - g) * at(&MAGENTA) + (b - r) * at(&BLUE) } else { (b - g) * at(&MAGENTA) + (r - b) * at(&RED) }
+    } else {
+        b * at(&WHITE) + if r <= g { (r - b) * at(&YELLOW) + (g - r) * at(&GREEN) } else { (g - b) * at(&YELLOW) + (r - g) * at(&RED) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::random_f64;
+
+    #[test]
+    fn test_film_weight() {
+        // Evenly spread light averages out to white
+        let n = 200_000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            sum += (1.0 / n as f64) * film_weight(sample_wavelength(random_f64()));
+        }
+        for c in [sum.r, sum.g, sum.b] {
+            assert!((c - 1.0).abs() < 0.02, "{c}");
+        }
+
+        // Single wavelengths land on the right hue
+        let blue = film_weight(450.0);
+        assert!(blue.b > blue.g && blue.b > blue.r);
+        let green = film_weight(530.0);
+        assert!(green.g > green.r && green.g > green.b);
+        let red = film_weight(620.0);
+        assert!(red.r > red.g && red.r > red.b);
+    }
+
+    #[test]
+    fn test_rgb_round_trip() {
+        // Upsampled, then seen through the film, colours come back close to
+        // what they were; greys exactly
+        let steps = 2000;
+        let seen = |c: Color| {
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            for i in 0..steps {
+                let l = sample_wavelength((i as f64 + 0.5) / steps as f64);
+                sum += (rgb_to_spectrum(c, l) / steps as f64) * film_weight(l);
+            }
+            sum
+        };
+        let grey = seen(Color::new(0.5, 0.5, 0.5));
+        for got in [grey.r, grey.g, grey.b] {
+            assert!((got - 0.5).abs() < 1e-3, "{got}");
+        }
+        for c in [Color::new(0.8, 0.2, 0.1), Color::new(0.1, 0.7, 0.3), Color::new(0.2, 0.3, 0.9), Color::new(0.9, 0.8, 0.2)] {
+            let got = seen(c);
+            for (a, b) in [(got.r, c.r), (got.g, c.g), (got.b, c.b)] {
+                assert!((a - b).abs() < 0.1, "{a} vs {b}");
+            }
+        }
+    }
+}
@@ -1,30 +1,42 @@
+use std::sync::Arc;
+use std::f64::consts::PI;
 use crate::vec3::{Point3,Vec3};
-use crate::hittable::{Hittable, HitRecord};
+use crate::hittable::{Hittable, HitRecord, LightSample};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::util::random_f64;
 
-pub struct Sphere<'a> {
+pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
-    pub mat: Box<dyn Material + 'a>
+    pub mat: Arc<dyn Material>
 }
 
-impl <'a>Sphere<'a> {
-    pub fn new(center: Point3, radius: f64, mat: impl Material + 'a) -> Sphere<'a> {
-        Sphere{ center, radius, mat: Box::new(mat) }
+impl Sphere {
+    pub fn new(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Sphere {
+        Sphere{ center, radius, mat }
     }
 }
 
-impl <'a>Hittable for Sphere<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+// Longitude and latitude of a point on the unit sphere, both in [0, 1]:
+// u goes around the y axis starting from -x, v runs from the bottom pole up
+fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let oc = self.center - r.origin;
         let a = r.direction.length_squared();
         let h = Vec3::dot(r.direction, oc);
         let c = oc.length_squared()  - self.radius*self.radius;
         let discriminant = h*h - a*c;
         if discriminant < 0.0 {
-            return None
+            None
         }else {
             let sqrtd = discriminant.sqrt();
             let mut root = (h - sqrtd) / a;
@@ -38,8 +50,55 @@ impl <'a>Hittable for Sphere<'a> {
             let p = r.at(t);
             let normal = (p - self.center) / self.radius;
             let mut rec= HitRecord::new(t, p, normal, &*self.mat);
+            (rec.u, rec.v) = sphere_uv(normal);
             rec.set_face_normal(r);
             Some(rec)
         }
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::from_points(self.center - rvec, self.center + rvec))
+    }
+
+    // Samples the cone of directions the sphere covers, which wastes nothing on
+    // the far side. From inside, the whole surface is sampled instead.
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let d = self.center - origin;
+        let dist_squared = d.length_squared();
+        let r2 = self.radius * self.radius;
+        if dist_squared <= r2 {
+            let normal = Vec3::random_unit_vector();
+            return LightSample::from_area(origin, self.center + self.radius * normal, normal, 4.0 * PI * r2);
+        }
+
+        // 1 - cos(theta_max), written so it doesn't cancel out for small or distant spheres
+        let sin2_max = r2 / dist_squared;
+        let one_minus_cos_max = sin2_max / (1.0 + (1.0 - sin2_max).sqrt());
+        let cos_theta = 1.0 - random_f64() * one_minus_cos_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_f64();
+
+        let w = d / dist_squared.sqrt();
+        let (u, v) = Vec3::orthonormal_basis(w);
+        let direction = cos_theta * w + sin_theta * (phi.cos() * u + phi.sin() * v);
+
+        // Nearest point on the sphere in that direction
+        let b = Vec3::dot(direction, d);
+        let t = b - (b * b - (dist_squared - r2)).max(0.0).sqrt();
+        Some(LightSample { p: origin + t * direction, pdf: 1.0 / (2.0 * PI * one_minus_cos_max) })
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let r2 = self.radius * self.radius;
+        let dist_squared = (self.center - r.origin).length_squared();
+        if dist_squared <= r2 {
+            return LightSample::area_pdf(self, r, ray_t, 4.0 * PI * r2);
+        }
+        if self.hit(r, ray_t).is_none() {
+            return 0.0;
+        }
+        let sin2_max = r2 / dist_squared;
+        1.0 / (2.0 * PI * sin2_max / (1.0 + (1.0 - sin2_max).sqrt()))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use crate::color::Color;
+use crate::vec3::Point3;
+
+// Colour as a function of surface coordinates (u, v) and the hit point p
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> SolidColor {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.albedo
+    }
+}
+
+// Alternating cubes of size `scale` filling space, so the pattern doesn't
+// depend on how a primitive maps its uvs
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> CheckerTexture {
+        CheckerTexture { inv_scale: 1.0 / scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Checkerboard in uv space: `columns` squares across u in [0, 1] and `rows`
+// across v, repeating outside that range
+pub struct UvCheckerTexture {
+    columns: f64,
+    rows: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl UvCheckerTexture {
+    pub fn new(columns: f64, rows: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> UvCheckerTexture {
+        UvCheckerTexture { columns, rows, even, odd }
+    }
+}
+
+impl Texture for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let i = (u * self.columns).floor() as i64;
+        let j = (v * self.rows).floor() as i64;
+        if (i + j) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn black_and_white() -> (Arc<dyn Texture>, Arc<dyn Texture>) {
+        (Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))), Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))))
+    }
+
+    #[test]
+    fn test_checkers() {
+        let (black, white) = black_and_white();
+        let checker = CheckerTexture::new(0.5, black, white);
+        let at = |x, y, z| checker.value(0.0, 0.0, Point3::new(x, y, z)).r;
+        assert_eq!(at(0.1, 0.1, 0.1), 0.0);
+        assert_eq!(at(0.6, 0.1, 0.1), 1.0);
+        assert_eq!(at(0.6, 0.6, 0.1), 0.0);
+        // Negative coordinates keep alternating across zero
+        assert_eq!(at(-0.1, 0.1, 0.1), 1.0);
+
+        let (black, white) = black_and_white();
+        let checker = UvCheckerTexture::new(4.0, 2.0, black, white);
+        let at = |u, v| checker.value(u, v, Point3::new(0.0, 0.0, 0.0)).r;
+        assert_eq!(at(0.1, 0.1), 0.0);
+        assert_eq!(at(0.3, 0.1), 1.0);
+        assert_eq!(at(0.3, 0.6), 0.0);
+        assert_eq!(at(-0.1, 0.1), 1.0);
+    }
+}
@@ -0,0 +1,183 @@
+use glam::{DMat3, DMat4, DVec3};
+use crate::vec3::{Point3, Vec3};
+use crate::hittable::{Hittable, HitRecord, LightSample};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::aabb::Aabb;
+
+// Moves, turns or stretches any object with a 4x4 affine matrix. Rays are
+// taken into the object's own space and hits brought back out, so the object
+// itself never knows it was transformed.
+pub struct Transform {
+    object: Box<dyn Hittable>,
+    matrix: DMat4,   // object space to world space
+    inverse: DMat4,
+    // Inverse transpose of the linear part, which keeps normals perpendicular
+    // to the surface under non-uniform scaling
+    normal_matrix: DMat3,
+    // Only rotation, translation and uniform scale: angles are kept, so light
+    // sample densities per solid angle carry over unchanged
+    similarity: bool,
+}
+
+impl Transform {
+    pub fn new(object: impl Hittable + 'static, matrix: DMat4) -> Transform {
+        assert!(matrix.determinant().abs() > 1e-12, "transform matrix can't be inverted");
+        let inverse = matrix.inverse();
+        let linear = DMat3::from_mat4(matrix);
+        let gram = linear.transpose() * linear;
+        let scale = gram.x_axis.x;
+        let similarity = gram.abs_diff_eq(DMat3::from_diagonal(DVec3::splat(scale)), 1e-9 * scale.max(1.0));
+        Transform {
+            object: Box::new(object),
+            matrix,
+            inverse,
+            normal_matrix: DMat3::from_mat4(inverse).transpose(),
+            similarity,
+        }
+    }
+
+    fn to_world(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point3(p.into()).into()
+    }
+
+    // The direction isn't normalised, so t means the same in both spaces
+    fn to_local(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point3(r.origin.into()).into(),
+            self.inverse.transform_vector3(r.direction.into()).into(),
+        )
+    }
+}
+
+// Matrices for Transform::new; combine them with `*`, the rightmost is applied first
+pub fn translate(offset: Vec3) -> DMat4 {
+    DMat4::from_translation(offset.into())
+}
+
+pub fn rotate(axis: Vec3, degrees: f64) -> DMat4 {
+    DMat4::from_axis_angle(DVec3::from(axis).normalize(), degrees.to_radians())
+}
+
+pub fn scale(factors: Vec3) -> DMat4 {
+    DMat4::from_scale(factors.into())
+}
+
+// Puts the origin at `from` and turns the object so its +z axis points at
+// `to`, with +y leaning towards `up`
+pub fn look_at(from: Point3, to: Point3, up: Vec3) -> DMat4 {
+    let w = (to - from).unit_vector();
+    let u = Vec3::cross(up, w).unit_vector();
+    let v = Vec3::cross(w, u);
+    DMat4::from_cols(
+        DVec3::from(u).extend(0.0),
+        DVec3::from(v).extend(0.0),
+        DVec3::from(w).extend(0.0),
+        DVec3::from(from).extend(1.0),
+    )
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = self.object.hit(&self.to_local(r), ray_t)?;
+        rec.p = self.to_world(rec.p);
+        // The normal already faces against the ray; the inverse transpose keeps
+        // it that way (n.d is unchanged), so front_face stays valid
+        rec.normal = (self.normal_matrix * DVec3::from(rec.normal)).normalize().into();
+        Some(rec)
+    }
+
+    // The box around the object's transformed bounding box corners
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.object.bounding_box()?;
+        let mut min = DVec3::INFINITY;
+        let mut max = DVec3::NEG_INFINITY;
+        for i in 0..8 {
+            let pick = |axis: Interval, bit: usize| if i & bit == 0 { axis.min } else { axis.max };
+            let corner = self.matrix.transform_point3(DVec3::new(pick(b.x, 1), pick(b.y, 2), pick(b.z, 4)));
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+        Some(Aabb::from_points(min.into(), max.into()))
+    }
+
+    // Stretched lights would need their densities reworked, so only objects
+    // moved without distortion can be sampled
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        if !self.similarity {
+            return None;
+        }
+        let sample = self.object.sample(self.inverse.transform_point3(origin.into()).into())?;
+        Some(LightSample { p: self.to_world(sample.p), pdf: sample.pdf })
+    }
+
+    // Angles are kept by a similarity, so the density carries over as is
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        if !self.similarity {
+            return 0.0;
+        }
+        self.object.sample_pdf(&self.to_local(r), ray_t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use crate::cube::Cube;
+    use crate::sphere::Sphere;
+    use crate::material::{Material, Lambertian};
+    use crate::color::Color;
+
+    fn grey() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0))
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_rotated_box() {
+        // Unit cube turned 45 degrees about y, then moved up by 2
+        let cube = Cube::from_center_size(Point3::new(0.0, 0.0, 0.0), 1.0, grey());
+        let turned = Transform::new(cube, translate(Vec3::new(0.0, 2.0, 0.0)) * rotate(Vec3::new(0.0, 1.0, 0.0), 45.0));
+
+        // Along -z its nearest point is now an edge, sqrt(0.5) from the centre
+        let r = Ray::new(Point3::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = turned.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!((rec.t - (5.0 - 0.5f64.sqrt())).abs() < 1e-9);
+        assert!(close(rec.p, Point3::new(0.0, 2.0, 0.5f64.sqrt())));
+
+        // Slightly to the side, the hit is on a face turned 45 degrees
+        let r = Ray::new(Point3::new(0.2, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = turned.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        let h = 0.5f64.sqrt();
+        assert!(close(rec.normal, Vec3::new(h, 0.0, h)));
+        assert!(rec.front_face);
+
+        let b = turned.bounding_box().unwrap();
+        assert!((b.x.max - h).abs() < 1e-9 && (b.y.min - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_scaled_normals_and_look_at() {
+        // A sphere squashed into an ellipsoid twice as wide as it is tall
+        let ellipsoid = Transform::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, grey()), scale(Vec3::new(2.0, 1.0, 1.0)));
+        assert!(ellipsoid.sample(Point3::new(0.0, 5.0, 0.0)).is_none());
+
+        // On x^2/4 + y^2 = 1 the normal is along (x/4, y)
+        let p = Point3::new(2.0f64.sqrt(), 0.5f64.sqrt(), 0.0);
+        let r = Ray::new(Point3::new(p.x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = ellipsoid.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(close(rec.p, p));
+        assert!(close(rec.normal, Vec3::new(p.x / 4.0, p.y, 0.0).unit_vector()));
+
+        let m = look_at(Point3::new(1.0, 1.0, 1.0), Point3::new(1.0, 1.0, 5.0), Vec3::new(0.0, 1.0, 0.0));
+        let z: Vec3 = m.transform_vector3(DVec3::Z).into();
+        let origin: Vec3 = m.transform_point3(DVec3::ZERO).into();
+        assert!(close(z, Vec3::new(0.0, 0.0, 1.0)) && close(origin, Point3::new(1.0, 1.0, 1.0)));
+        let ball = Transform::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5, grey()), m);
+        assert!(ball.sample(Point3::new(0.0, 0.0, -5.0)).is_some());
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+use crate::vec3::{Point3, Vec3};
+use crate::hittable::{Hittable, HitRecord, LightSample};
+use crate::hittable_list::HittableList;
+use crate::bvh::BvhNode;
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::util::random_f64;
+
+// Vertex buffers shared by all triangles of a mesh. `normals` and `uvs` are
+// either empty or hold one entry per position; each face indexes all three.
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+struct SharedMesh {
+    data: MeshData,
+    mat: Arc<dyn Material>,
+}
+
+impl SharedMesh {
+    fn face_vertices(&self, face: usize) -> [Point3; 3] {
+        let [i0, i1, i2] = self.data.indices[face];
+        let p = &self.data.positions;
+        [p[i0], p[i1], p[i2]]
+    }
+
+    fn face_area(&self, face: usize) -> f64 {
+        let [p0, p1, p2] = self.face_vertices(face);
+        0.5 * Vec3::cross(p1 - p0, p2 - p0).length()
+    }
+
+    // Uniformly distributed point on a face, with the face's geometric normal
+    fn sample_face(&self, face: usize) -> (Point3, Vec3) {
+        let [p0, p1, p2] = self.face_vertices(face);
+        let s = random_f64().sqrt();
+        let t = random_f64();
+        let (b1, b2) = (s * (1.0 - t), s * t);
+        let p = (1.0 - s) * p0 + b1 * p1 + b2 * p2;
+        (p, Vec3::cross(p1 - p0, p2 - p0).unit_vector())
+    }
+}
+
+// One face of a mesh; only holds a handle to the shared buffers
+pub struct Triangle {
+    mesh: Arc<SharedMesh>,
+    face: usize,
+}
+
+impl Triangle {
+    // A stand-alone triangle, wound counter-clockwise when seen from the front
+    pub fn new(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Triangle {
+        let data = MeshData { positions: vec![a, b, c], indices: vec![[0, 1, 2]], ..Default::default() };
+        Triangle { mesh: Arc::new(SharedMesh { data, mat }), face: 0 }
+    }
+
+    fn vertices(&self) -> [Point3; 3] {
+        self.mesh.face_vertices(self.face)
+    }
+}
+
+impl Hittable for Triangle {
+    // Watertight intersection (Woop, Benthin and Wald 2013): the triangle is
+    // sheared into a space where the ray runs along +z from the origin, so
+    // neighbouring triangles evaluate exactly the same edge functions and no
+    // ray can slip through a shared edge.
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let [p0, p1, p2] = self.vertices();
+        let d = r.direction;
+
+        // Make the largest direction component z
+        let kz = if d.x.abs() > d.y.abs() {
+            if d.x.abs() > d.z.abs() { 0 } else { 2 }
+        } else if d.y.abs() > d.z.abs() {
+            1
+        } else {
+            2
+        };
+        let kx = (kz + 1) % 3;
+        let ky = (kx + 1) % 3;
+        let permute = |v: Vec3| Vec3::new(v[kx], v[ky], v[kz]);
+
+        let d = permute(d);
+        let sx = -d.x / d.z;
+        let sy = -d.y / d.z;
+        let sz = 1.0 / d.z;
+        let shear = |p: Point3| {
+            let p = permute(p - r.origin);
+            Vec3::new(p.x + sx * p.z, p.y + sy * p.z, p.z * sz)
+        };
+        let (t0, t1, t2) = (shear(p0), shear(p1), shear(p2));
+
+        // Edge functions; the ray is inside when all three share a sign
+        let e0 = t1.x * t2.y - t1.y * t2.x;
+        let e1 = t2.x * t0.y - t2.y * t0.x;
+        let e2 = t0.x * t1.y - t0.y * t1.x;
+        if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+            return None;
+        }
+        let det = e0 + e1 + e2;
+        if det == 0.0 {
+            return None;
+        }
+
+        let t = (e0 * t0.z + e1 * t1.z + e2 * t2.z) / det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+        let (b0, b1, b2) = (e0 / det, e1 / det, e2 / det);
+
+        let data = &self.mesh.data;
+        let [i0, i1, i2] = data.indices[self.face];
+        let p = b0 * p0 + b1 * p1 + b2 * p2;
+        let geometric = Vec3::cross(p1 - p0, p2 - p0).unit_vector();
+        let mut rec = HitRecord::new(t, p, geometric, &*self.mesh.mat);
+        rec.front_face = Vec3::dot(r.direction, geometric) < 0.0;
+
+        // Smooth shading: interpolate vertex normals, kept on the geometric side
+        let mut normal = geometric;
+        if !data.normals.is_empty() {
+            let n = b0 * data.normals[i0] + b1 * data.normals[i1] + b2 * data.normals[i2];
+            if !n.near_zero() {
+                let n = n.unit_vector();
+                normal = if Vec3::dot(n, geometric) < 0.0 { -n } else { n };
+            }
+        }
+        rec.normal = if rec.front_face { normal } else { -normal };
+
+        // Interpolated texture coordinates, or the barycentrics without them
+        (rec.u, rec.v) = if data.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let (uv0, uv1, uv2) = (data.uvs[i0], data.uvs[i1], data.uvs[i2]);
+            (b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0, b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1)
+        };
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = self.vertices();
+        let a = Aabb::from_points(p0, p1);
+        Some(Aabb::surrounding(&a, &Aabb::from_points(p2, p2)))
+    }
+
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let (p, normal) = self.mesh.sample_face(self.face);
+        LightSample::from_area(origin, p, normal, self.mesh.face_area(self.face))
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        LightSample::area_pdf(self, r, ray_t, self.mesh.face_area(self.face))
+    }
+}
+
+// Many triangles sharing one set of vertex buffers and one material
+pub struct TriangleMesh {
+    bvh: BvhNode,
+    mesh: Arc<SharedMesh>,
+    // Running total of face areas, for picking faces as a light
+    area_cdf: Vec<f64>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData, mat: Arc<dyn Material>) -> TriangleMesh {
+        let n = data.positions.len();
+        assert!(data.normals.is_empty() || data.normals.len() == n, "mesh needs one normal per position");
+        assert!(data.uvs.is_empty() || data.uvs.len() == n, "mesh needs one uv per position");
+        assert!(data.indices.iter().flatten().all(|&i| i < n), "mesh face index out of range");
+
+        let faces = data.indices.len();
+        let mesh = Arc::new(SharedMesh { data, mat });
+        let mut triangles = HittableList::new();
+        let mut area_cdf = Vec::with_capacity(faces);
+        let mut total = 0.0;
+        for face in 0..faces {
+            triangles.add(Triangle { mesh: Arc::clone(&mesh), face });
+            total += mesh.face_area(face);
+            area_cdf.push(total);
+        }
+        TriangleMesh { bvh: BvhNode::new(triangles), mesh, area_cdf }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+
+    // Uniform over the whole surface: faces are picked in proportion to their area
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let total = *self.area_cdf.last()?;
+        let target = random_f64() * total;
+        let face = self.area_cdf.partition_point(|&a| a <= target).min(self.area_cdf.len() - 1);
+        let (p, normal) = self.mesh.sample_face(face);
+        LightSample::from_area(origin, p, normal, total)
+    }
+
+    fn sample_pdf(&self, r: &Ray, ray_t: Interval) -> f64 {
+        self.area_cdf.last().map_or(0.0, |&total| LightSample::area_pdf(self, r, ray_t, total))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+    use crate::util::random_f64;
+
+    fn grey() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5), 0.0))
+    }
+
+    // Unit square in the xy plane split along its diagonal, normals bent outwards
+    fn quad() -> MeshData {
+        MeshData {
+            positions: vec![
+                Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![
+                Vec3::new(-1.0, -1.0, 1.0), Vec3::new(1.0, -1.0, 1.0),
+                Vec3::new(1.0, 1.0, 1.0), Vec3::new(-1.0, 1.0, 1.0),
+            ],
+            uvs: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+        }
+    }
+
+    #[test]
+    fn test_shared_edge_is_watertight() {
+        let mesh = TriangleMesh::new(quad(), grey());
+        for _ in 0..1000 {
+            // Aim exactly at the diagonal both triangles share
+            let s = random_f64();
+            let target = Point3::new(s, s, 0.0);
+            let origin = Vec3::random_range(-3.0, 3.0) + Point3::new(0.0, 0.0, 5.0);
+            let r = Ray::new(origin, target - origin);
+            assert!(mesh.hit(&r, Interval::new(0.001, f64::INFINITY)).is_some());
+        }
+    }
+
+    #[test]
+    fn test_interpolated_normals() {
+        let mesh = TriangleMesh::new(quad(), grey());
+        let r = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal.z - 1.0).abs() < 1e-9);
+
+        // From behind, the shading normal flips with the geometric one
+        let r = Ray::new(Point3::new(0.9, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = mesh.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(!rec.front_face);
+        assert!(rec.normal.z < 0.0 && rec.normal.x < 0.0);
+
+        let tri = Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), grey());
+        let miss = Ray::new(Point3::new(0.8, 0.8, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(tri.hit(&miss, Interval::new(0.001, f64::INFINITY)).is_none());
+    }
+}
@@ -4,4 +4,10 @@ pub fn random_f64() -> f64 {
 
 pub fn random_f64_range(min: f64, max: f64) -> f64 {
     min + (max - min) * random_f64()
-}
\ No newline at end of file
+}
+
+// A generator of its own, so what is drawn from it depends only on `seed`
+// and not on what else the program has drawn
+pub fn rng(seed: u64) -> fastrand::Rng {
+    fastrand::Rng::with_seed(seed)
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, Mul, Div, Sub, Neg};
+use std::ops::{Add, Mul, Div, Sub, Neg, Index};
 use crate::util::{random_f64, random_f64_range};
 //use crate::random::*;
 
@@ -31,8 +31,8 @@ impl Vec3 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
     pub fn near_zero(&self) -> bool {
-        const s: f64 = 1e-8;
-        self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
+        const S: f64 = 1e-8;
+        self.x.abs() < S && self.y.abs() < S && self.z.abs() < S
     }
     pub fn unit_vector(self) -> Vec3 {
         self / self.length()
@@ -81,6 +81,12 @@ impl Vec3 {
             }
         }
     }
+    // Two unit vectors that complete the unit vector `w` to an orthonormal basis
+    pub fn orthonormal_basis(w: Vec3) -> (Vec3, Vec3) {
+        let a = if w.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = Vec3::cross(w, a).unit_vector();
+        (Vec3::cross(w, v), v)
+    }
     
 }
 
@@ -112,6 +118,18 @@ impl Div<f64> for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {axis}"),
+        }
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Vec3;
     fn neg(self) -> Vec3 {
@@ -119,6 +137,19 @@ impl Neg for Vec3 {
     }
 }
 
+// Conversions for the matrix maths in glam
+impl From<Vec3> for glam::DVec3 {
+    fn from(v: Vec3) -> glam::DVec3 {
+        glam::DVec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<glam::DVec3> for Vec3 {
+    fn from(v: glam::DVec3) -> Vec3 {
+        Vec3::new(v.x, v.y, v.z)
+    }
+}
+
 //type alias
 pub type Point3 = Vec3;
 